@@ -0,0 +1,398 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Order, Reply,
+    Response, StdResult, SubMsg, WasmMsg
+};
+use cw2::set_contract_version;
+use cw_storage_plus::Bound;
+use cw_utils::parse_reply_instantiate_data;
+
+use crate::error::ContractError;
+use crate::msg::{ConfigResponse, ExecuteMsg, InstantiateMsg, PoolsResponse, QueryMsg};
+use crate::state::{
+    Config, PendingPool, PoolMetadata, PoolRecord, CONFIG, PENDING_POOL, POOLS,
+    POOLS_BY_COLLECTION
+};
+
+const CONTRACT_NAME: &str = "NFT_STAKING_POOL_FACTORY";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const INSTANTIATE_POOL_REPLY_ID: u64 = 1;
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    let owner = match msg.owner {
+        Some(owner) => deps.api.addr_validate(&owner)?.to_string(),
+        None => info.sender.to_string(),
+    };
+    CONFIG.save(deps.storage, &Config {
+        owner,
+        pool_code_id: msg.pool_code_id,
+    })?;
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::CreatePool { label, metadata, config } => {
+            execute_create_pool(deps, env, info, label, metadata, *config)
+        }
+        ExecuteMsg::UpdatePoolMetadata { address, metadata } => {
+            execute_update_pool_metadata(deps, info, address, metadata)
+        }
+        ExecuteMsg::RemovePool { address } => execute_remove_pool(deps, info, address),
+        ExecuteMsg::SetPoolCodeId { code_id } => execute_set_pool_code_id(deps, info, code_id),
+        ExecuteMsg::SetOwner { address } => execute_set_owner(deps, info, address),
+    }
+}
+
+fn execute_create_pool(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    label: String,
+    metadata: PoolMetadata,
+    mut config: Hope_Contract::msg::InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let factory = CONFIG.load(deps.storage)?;
+
+    if info.sender != factory.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    // the reply can only tell one pending pool apart
+    if PENDING_POOL.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::PoolPending {});
+    }
+    let nft_address = deps.api.addr_validate(&config.nft_address)?.to_string();
+    // pools are administered by the factory owner unless the config says otherwise
+    if config.owner.is_none() {
+        config.owner = Some(factory.owner.clone());
+    }
+
+    PENDING_POOL.save(deps.storage, &PendingPool {
+        code_id: factory.pool_code_id,
+        nft_address: nft_address.clone(),
+        token_address: config.token_address.clone(),
+        metadata,
+    })?;
+
+    let instantiate = WasmMsg::Instantiate {
+        admin: Some(factory.owner),
+        code_id: factory.pool_code_id,
+        msg: to_json_binary(&config)?,
+        funds: vec![],
+        label,
+    };
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(instantiate, INSTANTIATE_POOL_REPLY_ID))
+        .add_attribute("action", "create_pool")
+        .add_attribute("nft_address", nft_address))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if msg.id != INSTANTIATE_POOL_REPLY_ID {
+        return Err(ContractError::UnknownReply { id: msg.id });
+    }
+    // the pool instantiates its receipt collection too, so its own address is
+    // taken from the reply data rather than from the instantiate events
+    let address = parse_reply_instantiate_data(msg)
+        .map_err(|err| ContractError::InstantiateFailed { error: err.to_string() })?
+        .contract_address;
+
+    let pending = PENDING_POOL.load(deps.storage)?;
+    PENDING_POOL.remove(deps.storage);
+
+    let record = PoolRecord {
+        address: address.clone(),
+        code_id: pending.code_id,
+        nft_address: pending.nft_address,
+        token_address: pending.token_address,
+        metadata: pending.metadata,
+        created_at: env.block.time.seconds(),
+    };
+    POOLS.save(deps.storage, &address, &record)?;
+    POOLS_BY_COLLECTION.save(deps.storage, (&record.nft_address, &address), &Empty {})?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_pool")
+        .add_attribute("pool", address))
+}
+
+fn execute_update_pool_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    metadata: PoolMetadata,
+) -> Result<Response, ContractError> {
+    let factory = CONFIG.load(deps.storage)?;
+
+    if info.sender != factory.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    POOLS.update(deps.storage, &address, |record| -> Result<_, ContractError> {
+        match record {
+            Some(mut record) => {
+                record.metadata = metadata;
+                Ok(record)
+            }
+            None => Err(ContractError::UnknownPool { address: address.clone() }),
+        }
+    })?;
+    Ok(Response::default())
+}
+
+fn execute_remove_pool(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let factory = CONFIG.load(deps.storage)?;
+
+    if info.sender != factory.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let record = match POOLS.may_load(deps.storage, &address)? {
+        Some(record) => record,
+        None => return Err(ContractError::UnknownPool { address }),
+    };
+    POOLS.remove(deps.storage, &address);
+    POOLS_BY_COLLECTION.remove(deps.storage, (&record.nft_address, &address));
+    Ok(Response::new()
+        .add_attribute("action", "remove_pool")
+        .add_attribute("pool", address))
+}
+
+fn execute_set_pool_code_id(
+    deps: DepsMut,
+    info: MessageInfo,
+    code_id: u64,
+) -> Result<Response, ContractError> {
+    let factory = CONFIG.load(deps.storage)?;
+
+    if info.sender != factory.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    CONFIG.update(deps.storage, |mut factory| -> StdResult<_> {
+        factory.pool_code_id = code_id;
+        Ok(factory)
+    })?;
+    Ok(Response::default())
+}
+
+fn execute_set_owner(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let factory = CONFIG.load(deps.storage)?;
+
+    if info.sender != factory.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let owner = deps.api.addr_validate(&address)?;
+    CONFIG.update(deps.storage, |mut factory| -> StdResult<_> {
+        factory.owner = owner.to_string();
+        Ok(factory)
+    })?;
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::Pool { address } => to_json_binary(&POOLS.load(deps.storage, &address)?),
+        QueryMsg::Pools { start_after, limit } => to_json_binary(&query_pools(deps, start_after, limit)?),
+        QueryMsg::PoolsByCollection { collection, start_after, limit } => {
+            to_json_binary(&query_pools_by_collection(deps, collection, start_after, limit)?)
+        }
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    CONFIG.load(deps.storage)
+}
+
+pub fn query_pools(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<PoolsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let pools = POOLS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, record)| record))
+        .collect::<StdResult<_>>()?;
+    Ok(PoolsResponse { pools })
+}
+
+pub fn query_pools_by_collection(
+    deps: Deps,
+    collection: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<PoolsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let pools = POOLS_BY_COLLECTION
+        .prefix(&collection)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|address| POOLS.load(deps.storage, &address?))
+        .collect::<StdResult<_>>()?;
+    Ok(PoolsResponse { pools })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{from_json, Event, SubMsgResponse, SubMsgResult};
+
+    fn pool_config(nft_address: &str) -> Hope_Contract::msg::InstantiateMsg {
+        Hope_Contract::msg::InstantiateMsg {
+            denom: "ujuno".to_string(),
+            staking_period: 1000,
+            reward_wallet: "reward_wallet".to_string(),
+            distribute_period: 100,
+            owner: None,
+            nft_address: nft_address.to_string(),
+            token_address: None,
+            can_stake: true,
+            min_stake_age: None,
+            nft_weight: None,
+            transfer_unstaking: None,
+            points_rate: None,
+            reward_expiry: None,
+            receipts: None,
+        }
+    }
+
+    fn metadata(name: &str) -> PoolMetadata {
+        PoolMetadata {
+            name: name.to_string(),
+            description: None,
+            url: None,
+        }
+    }
+
+    /// A reply carrying `MsgInstantiateContractResponse { contract_address }`,
+    /// with the events of a pool that also instantiated its receipt collection.
+    fn instantiated(address: &str) -> Reply {
+        let mut data = vec![0x0a, address.len() as u8];
+        data.extend_from_slice(address.as_bytes());
+        Reply {
+            id: INSTANTIATE_POOL_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![
+                    Event::new("instantiate")
+                        .add_attribute("_contract_address", format!("{}-receipts", address))
+                        .add_attribute("code_id", "8"),
+                    Event::new("instantiate")
+                        .add_attribute("_contract_address", address)
+                        .add_attribute("code_id", "7"),
+                ],
+                data: Some(Binary::from(data)),
+            }),
+        }
+    }
+
+    fn create_pool(deps: DepsMut, collection: &str, name: &str) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::CreatePool {
+            label: name.to_string(),
+            metadata: metadata(name),
+            config: Box::new(pool_config(collection)),
+        };
+        execute(deps, mock_env(), mock_info("creator", &[]), msg)
+    }
+
+    #[test]
+    fn creates_and_registers_pools() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { pool_code_id: 7, owner: None };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePool {
+            label: "pool".to_string(),
+            metadata: metadata("pool"),
+            config: Box::new(pool_config("collection1")),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("stranger", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = create_pool(deps.as_mut(), "collection1", "first").unwrap();
+        let err = create_pool(deps.as_mut(), "collection2", "second").unwrap_err();
+        assert!(matches!(err, ContractError::PoolPending {}));
+        let mut expected = pool_config("collection1");
+        expected.owner = Some("creator".to_string());
+        assert_eq!(res.messages, vec![SubMsg::reply_on_success(WasmMsg::Instantiate {
+            admin: Some("creator".to_string()),
+            code_id: 7,
+            msg: to_json_binary(&expected).unwrap(),
+            funds: vec![],
+            label: "first".to_string(),
+        }, INSTANTIATE_POOL_REPLY_ID)]);
+        reply(deps.as_mut(), mock_env(), instantiated("pool1")).unwrap();
+
+        create_pool(deps.as_mut(), "collection2", "second").unwrap();
+        reply(deps.as_mut(), mock_env(), instantiated("pool2")).unwrap();
+        create_pool(deps.as_mut(), "collection1", "third").unwrap();
+        reply(deps.as_mut(), mock_env(), instantiated("pool3")).unwrap();
+
+        let pools = query_pools(deps.as_ref(), None, None).unwrap().pools;
+        assert_eq!(pools.len(), 3);
+        assert_eq!(pools[0], PoolRecord {
+            address: "pool1".to_string(),
+            code_id: 7,
+            nft_address: "collection1".to_string(),
+            token_address: None,
+            metadata: metadata("first"),
+            created_at: mock_env().block.time.seconds(),
+        });
+
+        let pools = query_pools_by_collection(deps.as_ref(), "collection1".to_string(), None, None).unwrap().pools;
+        let addresses: Vec<String> = pools.into_iter().map(|pool| pool.address).collect();
+        assert_eq!(addresses, vec!["pool1", "pool3"]);
+        let pools = query_pools_by_collection(deps.as_ref(), "collection1".to_string(), Some("pool1".to_string()), None).unwrap().pools;
+        assert_eq!(pools.len(), 1);
+
+        let msg = ExecuteMsg::RemovePool { address: "pool1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let pools = query_pools_by_collection(deps.as_ref(), "collection1".to_string(), None, None).unwrap().pools;
+        assert_eq!(pools.len(), 1);
+
+        let err = reply(deps.as_mut(), mock_env(), Reply { id: 9, ..instantiated("pool4") }).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownReply { id: 9 }));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let msg = br#"{"update_pool_metadata":{"address":"pool1","metadata":{"name":"pool","website":"x"}}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+        let msg = br#"{"pool_code_id":7,"admin":"creator"}"#;
+        assert!(from_json::<InstantiateMsg>(msg).is_err());
+        let msg = br#"{"pools":{"limit":5,"offset":1}}"#;
+        assert!(from_json::<QueryMsg>(msg).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+use cosmwasm_std::StdError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("Unknown reply id {id}")]
+    UnknownReply { id: u64 },
+
+    #[error("Pool instantiation failed: {error}")]
+    InstantiateFailed { error: String },
+
+    #[error("Another pool is still being instantiated")]
+    PoolPending {},
+
+    #[error("Pool {address} is not registered")]
+    UnknownPool { address: String },
+}
@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Config, PoolMetadata, PoolRecord};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InstantiateMsg {
+    pub pool_code_id: u64,
+    /// Defaults to the sender of the instantiate message.
+    pub owner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ExecuteMsg {
+    /// Instantiates a staking pool with `config` and registers it.
+    CreatePool {
+        label: String,
+        metadata: PoolMetadata,
+        config: Box<Hope_Contract::msg::InstantiateMsg>,
+    },
+    UpdatePoolMetadata { address: String, metadata: PoolMetadata },
+    /// Drops a pool from the registry, the pool contract itself is untouched.
+    RemovePool { address: String },
+    SetPoolCodeId { code_id: u64 },
+    SetOwner { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum QueryMsg {
+    Config {},
+    Pool { address: String },
+    Pools { start_after: Option<String>, limit: Option<u32> },
+    PoolsByCollection { collection: String, start_after: Option<String>, limit: Option<u32> },
+}
+
+pub type ConfigResponse = Config;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolsResponse {
+    pub pools: Vec<PoolRecord>,
+}
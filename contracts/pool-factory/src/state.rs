@@ -0,0 +1,44 @@
+use cosmwasm_std::Empty;
+use cw_storage_plus::{Item, Map};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const CONFIG: Item<Config> = Item::new("config");
+/// Pool that is being instantiated, picked up again in the reply.
+pub const PENDING_POOL: Item<PendingPool> = Item::new("pending_pool");
+pub const POOLS: Map<&str, PoolRecord> = Map::new("pools");
+/// Secondary index of `POOLS` keyed by (collection, pool address).
+pub const POOLS_BY_COLLECTION: Map<(&str, &str), Empty> = Map::new("pools_by_collection");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub owner: String,
+    /// Code id of the staking pool contract every pool is created from.
+    pub pool_code_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PoolMetadata {
+    pub name: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingPool {
+    pub code_id: u64,
+    pub nft_address: String,
+    pub token_address: Option<String>,
+    pub metadata: PoolMetadata,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolRecord {
+    pub address: String,
+    pub code_id: u64,
+    pub nft_address: String,
+    pub token_address: Option<String>,
+    pub metadata: PoolMetadata,
+    pub created_at: u64,
+}
@@ -0,0 +1,120 @@
+//! A cw721-base collection whose minter is the staking contract. Every
+//! transfer of a receipt is reported back to it, and only it can burn.
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult, WasmMsg};
+use cw721_base::{ContractError, Cw721Contract, Extension, InstantiateMsg, QueryMsg};
+
+use crate::msg::MinterHookMsg;
+
+pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension>;
+type Receipts<'a> = Cw721Contract<'a, Extension, Empty>;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(deps: DepsMut, env: Env, info: MessageInfo, msg: InstantiateMsg) -> StdResult<Response> {
+    Receipts::default().instantiate(deps, env, info, msg)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> Result<Response, ContractError> {
+    let receipts = Receipts::default();
+    let minter = receipts.minter.load(deps.storage)?;
+
+    let moved = match &msg {
+        ExecuteMsg::TransferNft { token_id, .. } => Some(token_id.clone()),
+        // a receipt sent to the minter is redeemed, which closes the position
+        ExecuteMsg::SendNft { contract, token_id, .. } if *contract != minter => Some(token_id.clone()),
+        // a burnt receipt would strand its position
+        ExecuteMsg::Burn { .. } if info.sender != minter => return Err(ContractError::Unauthorized {}),
+        _ => None,
+    };
+    let response = receipts.execute(deps, env, info, msg)?;
+    match moved {
+        Some(token_id) => Ok(response.add_message(WasmMsg::Execute {
+            contract_addr: minter.to_string(),
+            msg: to_json_binary(&MinterHookMsg::ReceiptTransferred { token_id })?,
+            funds: vec![],
+        })),
+        None => Ok(response),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    Receipts::default().query(deps, env, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{from_json, CosmosMsg, OwnedDeps};
+    use cw721_base::MintMsg;
+
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            name: "Staked Hope".to_string(),
+            symbol: "SHOPE".to_string(),
+            minter: "staking".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let mint = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: "alice".to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("staking", &[]), mint).unwrap();
+        assert!(res.messages.is_empty());
+        deps
+    }
+
+    fn hooked(res: &Response) -> Vec<(String, MinterHookMsg)> {
+        res.messages
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+                    from_json(msg).ok().map(|hook| (contract_addr.clone(), hook))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transfers_are_reported_to_the_minter() {
+        let mut deps = setup();
+        let transfer = ExecuteMsg::TransferNft { recipient: "bob".to_string(), token_id: "1".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), transfer).unwrap();
+        let hook = MinterHookMsg::ReceiptTransferred { token_id: "1".to_string() };
+        assert_eq!(hooked(&res), [("staking".to_string(), hook.clone())]);
+
+        let send = ExecuteMsg::SendNft {
+            contract: "market".to_string(),
+            token_id: "1".to_string(),
+            msg: Binary::default(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), send).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(hooked(&res), [("staking".to_string(), hook)]);
+    }
+
+    #[test]
+    fn redeeming_is_not_a_transfer_and_only_the_minter_burns() {
+        let mut deps = setup();
+        let burn = ExecuteMsg::Burn { token_id: "1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), burn.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let send = ExecuteMsg::SendNft {
+            contract: "staking".to_string(),
+            token_id: "1".to_string(),
+            msg: Binary::default(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), send).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(hooked(&res).is_empty());
+        execute(deps.as_mut(), mock_env(), mock_info("staking", &[]), burn).unwrap();
+    }
+}
@@ -0,0 +1,10 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Sent to the minter, the staking contract, after a receipt changed hands so
+/// it can move the position in the same transaction.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum MinterHookMsg {
+    ReceiptTransferred { token_id: String },
+}
@@ -1,9 +1,19 @@
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use std::env::current_dir;
 use std::fs::create_dir_all;
+use std::path::Path;
 
-use Hope_Contract::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use Hope_Contract::state::State;
+use Hope_Contract::msg::{
+    AllTokenInfoResponse, AllTokensResponse, CarriedDustResponse, CollectionSetsResponse,
+    CurrentTimeResponse, DistributionHistoryResponse, ExecuteMsg, ExpiringRewardsResponse,
+    HooksResponse, InstantiateMsg, MigrateMsg, MyIdsResponse, MyInfoResponse, MySetsResponse,
+    PenaltyPolicyResponse, PenaltyPreviewResponse, PendingRewardsResponse,
+    PointSpendersResponse, PointSpendsResponse, PointsResponse, PoolStatsResponse,
+    PoolsResponse, QueryMsg, RafflesResponse, ReceiveMsg, ReceiveNftMsg, StakeChangedExecuteMsg,
+    StakerSnapshotResponse, TokenResponse, TotalPowerAtHeightResponse, UnbondingQueueResponse,
+    VestingInfoResponse, VotingPowerAtHeightResponse,
+};
+use Hope_Contract::state::{Raffle, StakePosition, State};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -11,8 +21,48 @@ fn main() {
     create_dir_all(&out_dir).unwrap();
     remove_schemas(&out_dir).unwrap();
 
-    export_schema(&schema_for!(InstantiateMsg), &out_dir);
-    export_schema(&schema_for!(ExecuteMsg), &out_dir);
-    export_schema(&schema_for!(QueryMsg), &out_dir);
-    export_schema(&schema_for!(State), &out_dir);
+    export_schemas(&out_dir);
+}
+
+/// Writes the schema of every message and query response. `tests/schema.rs`
+/// checks the committed files against it.
+pub fn export_schemas(out_dir: &Path) {
+    export_schema(&schema_for!(InstantiateMsg), out_dir);
+    export_schema(&schema_for!(ExecuteMsg), out_dir);
+    export_schema(&schema_for!(MigrateMsg), out_dir);
+    export_schema(&schema_for!(ReceiveMsg), out_dir);
+    export_schema(&schema_for!(ReceiveNftMsg), out_dir);
+    export_schema(&schema_for!(QueryMsg), out_dir);
+    export_schema(&schema_for!(StakeChangedExecuteMsg), out_dir);
+
+    // one response per query
+    export_schema(&schema_for!(State), out_dir);
+    export_schema(&schema_for!(AllTokensResponse), out_dir);
+    export_schema(&schema_for!(AllTokenInfoResponse), out_dir);
+    export_schema(&schema_for!(CurrentTimeResponse), out_dir);
+    export_schema(&schema_for!(TokenResponse), out_dir);
+    export_schema(&schema_for!(MyIdsResponse), out_dir);
+    export_schema(&schema_for!(MyInfoResponse), out_dir);
+    export_schema(&schema_for!(PendingRewardsResponse), out_dir);
+    export_schema(&schema_for!(PoolStatsResponse), out_dir);
+    export_schema(&schema_for!(DistributionHistoryResponse), out_dir);
+    export_schema(&schema_for!(CarriedDustResponse), out_dir);
+    export_schema(&schema_for!(PenaltyPolicyResponse), out_dir);
+    export_schema(&schema_for!(PenaltyPreviewResponse), out_dir);
+    export_schema(&schema_for!(PoolsResponse), out_dir);
+    export_schema(&schema_for!(StakePosition), out_dir);
+    export_schema(&schema_for!(UnbondingQueueResponse), out_dir);
+    export_schema(&schema_for!(VotingPowerAtHeightResponse), out_dir);
+    export_schema(&schema_for!(TotalPowerAtHeightResponse), out_dir);
+    export_schema(&schema_for!(HooksResponse), out_dir);
+    export_schema(&schema_for!(StakerSnapshotResponse), out_dir);
+    export_schema(&schema_for!(PointsResponse), out_dir);
+    export_schema(&schema_for!(PointSpendsResponse), out_dir);
+    export_schema(&schema_for!(PointSpendersResponse), out_dir);
+    export_schema(&schema_for!(VestingInfoResponse), out_dir);
+    export_schema(&schema_for!(ExpiringRewardsResponse), out_dir);
+    export_schema(&schema_for!(Raffle), out_dir);
+    export_schema(&schema_for!(RafflesResponse), out_dir);
+    export_schema(&schema_for!(CollectionSetsResponse), out_dir);
+    export_schema(&schema_for!(MySetsResponse), out_dir);
 }
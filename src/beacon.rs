@@ -0,0 +1,104 @@
+//! Rounds of the drand League of Entropy mainnet, the chained "default"
+//! network. A raffle fixes its round when it opens, so neither the owner nor
+//! whoever seals it can pick the randomness it is drawn with.
+use ark_bls12_381::{g2, Bls12_381, G1Affine, G2Affine, G2Projective};
+use ark_ec::hashing::curve_maps::wb::WBMap;
+use ark_ec::hashing::map_to_curve_hasher::MapToCurveBasedHasher;
+use ark_ec::hashing::HashToCurve;
+use ark_ec::pairing::Pairing;
+use ark_ec::AffineRepr;
+use ark_ff::field_hashers::DefaultFieldHasher;
+use ark_ff::Zero;
+use ark_serialize::CanonicalDeserialize;
+use sha2::{Digest, Sha256};
+
+/// Group public key of the network, a compressed G1 point.
+const PUBLIC_KEY: [u8; 48] = [
+    0x86, 0x8f, 0x00, 0x5e, 0xb8, 0xe6, 0xe4, 0xca, 0x0a, 0x47, 0xc8, 0xa7, 0x7c, 0xea, 0xa5, 0x30,
+    0x9a, 0x47, 0x97, 0x8a, 0x7c, 0x71, 0xbc, 0x5c, 0xce, 0x96, 0x36, 0x6b, 0x5d, 0x7a, 0x56, 0x99,
+    0x37, 0xc5, 0x29, 0xee, 0xda, 0x66, 0xc7, 0x29, 0x37, 0x84, 0xa9, 0x40, 0x28, 0x01, 0xaf, 0x31,
+];
+const DOMAIN: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+/// When round 1 was published.
+const GENESIS_TIME: u64 = 1595431050;
+/// Seconds between rounds.
+const PERIOD: u64 = 30;
+
+type G2Hasher = MapToCurveBasedHasher<G2Projective, DefaultFieldHasher<Sha256>, WBMap<g2::Config>>;
+
+/// The first round published at or after `time`.
+pub fn round_after(time: u64) -> u64 {
+    if time <= GENESIS_TIME {
+        return 1;
+    }
+    (time - GENESIS_TIME).div_ceil(PERIOD) + 1
+}
+
+/// Whether `signature` is the network's signature of `round`, which signs
+/// `sha256(previous_signature || round)`.
+pub fn verify(round: u64, previous_signature: &[u8], signature: &[u8]) -> bool {
+    let message = Sha256::new()
+        .chain_update(previous_signature)
+        .chain_update(round.to_be_bytes())
+        .finalize();
+    let public_key = match G1Affine::deserialize_compressed(&PUBLIC_KEY[..]) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    let signature = match G2Affine::deserialize_compressed(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let point = match G2Hasher::new(DOMAIN).and_then(|hasher| hasher.hash(&message)) {
+        Ok(point) => point,
+        Err(_) => return false,
+    };
+    // e(g1, signature) == e(public_key, H(message))
+    Bls12_381::multi_pairing([G1Affine::generator(), -public_key], [signature, point]).is_zero()
+}
+
+/// The randomness of a verified round.
+pub fn randomness(signature: &[u8]) -> [u8; 32] {
+    Sha256::digest(signature).into()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use cosmwasm_std::HexBinary;
+
+    /// Round 72785 of the mainnet, published at 1597614570.
+    pub(crate) const ROUND: u64 = 72785;
+    pub(crate) fn previous_signature() -> HexBinary {
+        HexBinary::from_hex(
+            "a609e19a03c2fcc559e8dae14900aaefe517cb55c840f6e69bc8e4f66c8d18e8a609685d9917efbfb0c37f058c2de88f\
+             13d297c7e19e0ab24813079efe57a182554ff054c7638153f9b26a60e7111f71a0ff63d9571704905d3ca6df0b031747",
+        )
+        .unwrap()
+    }
+    pub(crate) fn signature() -> HexBinary {
+        HexBinary::from_hex(
+            "82f5d3d2de4db19d40a6980e8aa37842a0e55d1df06bd68bddc8d60002e8e959eb9cfa368b3c1b77d18f02a54fe047b8\
+             0f0989315f83b12a74fd8679c4f12aae86eaf6ab5690b34f1fddd50ee3cc6f6cdf59e95526d5a5d82aaa84fa6f181e42",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rounds_follow_the_network_clock() {
+        assert_eq!(round_after(0), 1);
+        assert_eq!(round_after(GENESIS_TIME), 1);
+        assert_eq!(round_after(GENESIS_TIME + 1), 2);
+        assert_eq!(round_after(1597614570), ROUND);
+        assert_eq!(round_after(1597614571), ROUND + 1);
+    }
+
+    #[test]
+    fn verifies_mainnet_signatures() {
+        assert!(verify(ROUND, &previous_signature(), &signature()));
+        assert!(!verify(ROUND + 1, &previous_signature(), &signature()));
+        assert!(!verify(ROUND, &signature(), &signature()));
+        assert!(!verify(ROUND, &previous_signature(), &previous_signature()));
+        assert!(!verify(ROUND, &previous_signature(), &signature()[..48]));
+    }
+}
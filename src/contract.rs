@@ -1,46 +1,167 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    entry_point, to_binary,   CosmosMsg, Deps, DepsMut,Binary,
-    Env, MessageInfo, BankMsg, Response, StdResult, Uint128, WasmMsg, Coin, Order
+    from_json, to_json_binary, Api, BlockInfo, CosmosMsg, Deps, DepsMut, Binary,
+    Env, MessageInfo, BankMsg, Response, StdError, StdResult, Storage, Uint128, WasmMsg, Coin, Order,
+    OverflowError, QuerierWrapper, Reply
 };
 
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::expiry;
+use crate::hooks;
+use crate::math::{checked_add, checked_multiply_ratio, checked_sub};
+use crate::points;
+use crate::pools;
+use crate::raffle;
+use crate::receipts;
+use crate::sets;
+use crate::snapshot;
+use crate::vesting;
+use crate::voting;
+use crate::msg::{
+    ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, UpdateConfigMsg, PendingRewardsResponse, PoolStatsResponse,
+    DistributionHistoryResponse, CarriedDustResponse, PenaltyPreviewResponse, TokenResponse,
+    AllTokensResponse, AllTokenInfoResponse, CurrentTimeResponse, MyIdsResponse, MyInfoResponse,
+    PenaltyPolicyResponse, ReceiveNftMsg, StakeChangedHookMsg
+};
 use crate::state::{
-    State,CONFIG,TOKENINFO,OWNEDTOKEN, TokenInfo
+    State, CONFIG, TOKENINFO, OWNEDTOKEN, TokenInfo, DistributionEpoch, DISTRIBUTIONS,
+    DISTRIBUTION_COUNT, PenaltyPolicy, PenaltyDestination, PENALTY_POLICY, RafflePrize, TOTAL_POWER,
+    StakeInterval, STAKE_HISTORY, VESTING, VESTING_LOCKED,
 };
 use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
-use cw20::{Cw20ExecuteMsg};
+use cw_storage_plus::Bound;
 
 
 const CONTRACT_NAME: &str = "NFT_STAKING";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[entry_point]
+/// How many past distributions feed the `PoolStats` reward estimate.
+const RECENT_DISTRIBUTION_WINDOW: usize = 10;
+const DEFAULT_HISTORY_LIMIT: u32 = 10;
+const MAX_HISTORY_LIMIT: u32 = 30;
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+pub(crate) const DEFAULT_NFT_WEIGHT: u64 = 100;
+/// Upper bound for every configurable period and delay.
+const MAX_PERIOD: u64 = SECONDS_PER_YEAR;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    let owner = match msg.owner {
+        Some(owner) => validate_address(deps.api, "owner", &owner)?,
+        None => info.sender.to_string(),
+    };
+    let token_address = match msg.token_address {
+        Some(address) => Some(validate_address(deps.api, "token_address", &address)?),
+        None => None,
+    };
+    validate_denom(&msg.denom)?;
     let state = State {
-        owner:info.sender.to_string(),
-        denom:msg.denom,
-        staking_period : msg.staking_period,
-        distribute_period:msg.distribute_period,
-        reward_wallet : msg.reward_wallet,
-        total_staked : Uint128::new(0),
-        nft_address : "nft_address".to_string(),
-        token_address : "token_address".to_string(),
-        can_stake: true,
-        last_distribute:env.block.time.seconds()
+        owner,
+        denom: msg.denom,
+        staking_period: validate_period("staking_period", msg.staking_period, 1)?,
+        distribute_period: validate_period("distribute_period", msg.distribute_period, 1)?,
+        reward_wallet: validate_address(deps.api, "reward_wallet", &msg.reward_wallet)?,
+        total_staked: Uint128::new(0),
+        nft_address: validate_address(deps.api, "nft_address", &msg.nft_address)?,
+        token_address,
+        can_stake: msg.can_stake,
+        last_distribute: env.block.time.seconds(),
+        reward_dust: Uint128::zero(),
+        min_stake_age: validate_period("min_stake_age", msg.min_stake_age.unwrap_or_default(), 0)?,
+        nft_weight: msg.nft_weight.unwrap_or(DEFAULT_NFT_WEIGHT),
+        transfer_unstaking: msg.transfer_unstaking.unwrap_or(false),
+        points_rate: msg.points_rate.unwrap_or_default(),
+        reward_expiry: validate_period("reward_expiry", msg.reward_expiry.unwrap_or_default(), 0)?,
+        receipt_address: None
     };
-    CONFIG.save(deps.storage,&state)?;
-    Ok(Response::default())
+    CONFIG.save(deps.storage, &state)?;
+
+    let mut response = Response::new();
+    if let Some(config) = msg.receipts {
+        response = response.add_submessage(receipts::instantiate_receipts(&env, &state.owner, config)?);
+    }
+    Ok(response
+        .add_attribute("action", "instantiate")
+        .add_attribute("owner", state.owner)
+        .add_attribute("nft_address", state.nft_address))
+}
+
+/// Rewrites the config of an older deployment. Fields it predates are read
+/// with their defaults, `total_staked` is recounted since it no longer
+/// includes unstaking tokens, and stakers get the voting power they hold.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::WrongContract { contract: version.contract });
+    }
+
+    let mut state = CONFIG.load(deps.storage)?;
+    // the reward token used to be a plain string, empty when unset
+    if state.token_address.as_deref() == Some("") {
+        state.token_address = None;
+    }
+    let staked: Vec<TokenInfo> = TOKENINFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, token)| token))
+        .filter(|item| !matches!(item, Ok(token) if token.status != "Staked"))
+        .collect::<StdResult<_>>()?;
+    state.total_staked = Uint128::new(staked.len() as u128);
+    if TOTAL_POWER.may_load(deps.storage)?.is_none() {
+        for token in &staked {
+            voting::add_power(deps.storage, &token.owner, env.block.height)?;
+        }
+    }
+    // positions staked before their history was kept
+    let tokens = TOKENINFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, token)| token))
+        .collect::<StdResult<Vec<_>>>()?;
+    for token in tokens {
+        let mut history = STAKE_HISTORY.may_load(deps.storage, &token.owner)?.unwrap_or_default();
+        if history.iter().all(|interval| interval.token_id != token.token_id) {
+            history.push(StakeInterval {
+                token_id: token.token_id.clone(),
+                start: token.stake_time,
+                end: Some(token.unstake_time).filter(|_| token.status == "Unstaking"),
+            });
+            STAKE_HISTORY.save(deps.storage, &token.owner, &history)?;
+        }
+    }
+    if VESTING_LOCKED.may_load(deps.storage)?.is_none() {
+        let mut locked = Uint128::zero();
+        for item in VESTING.range(deps.storage, None, None, Order::Ascending) {
+            for tranche in item?.1 {
+                locked = locked.checked_add(tranche.amount.checked_sub(tranche.withdrawn)?)?;
+            }
+        }
+        VESTING_LOCKED.save(deps.storage, &locked)?;
+    }
+    CONFIG.save(deps.storage, &state)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", version.version))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        receipts::INSTANTIATE_RECEIPTS_REPLY_ID => receipts::reply_instantiate_receipts(deps, msg),
+        id => Err(ContractError::UnknownReply { id }),
+    }
 }
 
-#[entry_point]
+#[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
     env: Env,
@@ -48,23 +169,51 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::ReceiveNft(rcv_msg) => execute_stake_nft(deps, env, info, rcv_msg),
+        ExecuteMsg::ReceiveNft(rcv_msg) => execute_receive_nft(deps, env, info, rcv_msg),
         ExecuteMsg::UnstakeNft { token_id } => execute_unstake_nft(deps, env, info, token_id),
         ExecuteMsg::WithdrawNft { token_id } => execute_withdraw_nft(deps, env, info, token_id),
-        ExecuteMsg::GetReward {token_ids} =>execute_get_reward(deps, env, info, token_ids),
-        ExecuteMsg::DistributeReward {  } => execute_distribute_reward(deps,env,info),
-        ExecuteMsg::SetRewardWallet { address } => execute_reward_wallet(deps,env,info,address),
-        ExecuteMsg::SetNftAddress { address } => execute_nft_address(deps,env,info,address),
-        ExecuteMsg::SetTokenAddress { address } => execute_token_address(deps,env,info,address),
-        ExecuteMsg::SetOwner { address } => execute_set_owner(deps, env, info, address),
-        ExecuteMsg::WithdrawAllMoney { amount_juno} => execute_withdraw_all(deps,env, info, amount_juno),
-        ExecuteMsg::SetStakingPeriod { time } => execute_staking_period(deps,env,info,time),
-        ExecuteMsg::SetStake { flag } => execute_set_stake(deps,info,flag),
-        ExecuteMsg::SetDistributePeriod { time } => execute_distribute_period(deps, env, info, time)
+        ExecuteMsg::GetReward { token_ids, instant } => {
+            execute_get_reward(deps, env, info, token_ids, instant.unwrap_or(false))
+        }
+        ExecuteMsg::SyncReceipts { token_ids } => receipts::execute_sync_receipts(deps, env, token_ids),
+        ExecuteMsg::ReceiptTransferred { token_id } => receipts::execute_receipt_transferred(deps, env, info, token_id),
+        ExecuteMsg::TransferStake { token_id, recipient } => execute_transfer_stake(deps, env, info, token_id, recipient),
+        ExecuteMsg::DistributeReward {} => execute_distribute_reward(deps, env, info),
+        ExecuteMsg::UpdateConfig(update) => execute_update_config(deps, env, info, update),
+        ExecuteMsg::WithdrawAllMoney { amount_juno } => execute_withdraw_all(deps, env, info, amount_juno),
+        ExecuteMsg::SetPenaltyPolicy { policy } => execute_set_penalty_policy(deps, info, policy),
+        ExecuteMsg::Receive(wrapper) => pools::execute_receive(deps, env, info, wrapper),
+        ExecuteMsg::Unbond { pool, amount } => pools::execute_unbond(deps, env, info, pool, amount),
+        ExecuteMsg::WithdrawUnbonded { pool } => pools::execute_withdraw_unbonded(deps, env, info, pool),
+        ExecuteMsg::ClaimPoolRewards { pool, instant } => {
+            pools::execute_claim_pool_rewards(deps, env, info, pool, instant.unwrap_or(false))
+        }
+        ExecuteMsg::BondNative {} => pools::execute_bond_native(deps, env, info),
+        ExecuteMsg::SetPool { asset, weight } => pools::execute_set_pool(deps, info, asset, weight),
+        ExecuteMsg::AddHook { addr } => hooks::execute_add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => hooks::execute_remove_hook(deps, info, addr),
+        ExecuteMsg::SpendPoints { owner, amount, memo } => points::execute_spend_points(deps, env, info, owner, amount, memo),
+        ExecuteMsg::AddPointSpender { addr } => points::execute_add_point_spender(deps, info, addr),
+        ExecuteMsg::RemovePointSpender { addr } => points::execute_remove_point_spender(deps, info, addr),
+        ExecuteMsg::SetVestingPolicy { policy } => vesting::execute_set_vesting_policy(deps, info, policy),
+        ExecuteMsg::WithdrawVested {} => vesting::execute_withdraw_vested(deps, env, info),
+        ExecuteMsg::SweepExpiredRewards { token_ids, destination } => {
+            expiry::execute_sweep_expired(deps, env, info, token_ids, destination)
+        }
+        ExecuteMsg::CreateRaffle { commitment, draw_after } => {
+            raffle::execute_create_raffle(deps, env, info, commitment, draw_after)
+        }
+        ExecuteMsg::SealRaffle { raffle_id, previous_signature, signature } => {
+            raffle::execute_seal_raffle(deps, raffle_id, previous_signature, signature)
+        }
+        ExecuteMsg::DrawRaffle { raffle_id, secret } => raffle::execute_draw_raffle(deps, env, raffle_id, secret),
+        ExecuteMsg::CancelRaffle { raffle_id } => raffle::execute_cancel_raffle(deps, env, info, raffle_id),
+        ExecuteMsg::AddCollectionSet { set } => sets::execute_add_collection_set(deps, info, set),
+        ExecuteMsg::RemoveCollectionSet { name } => sets::execute_remove_collection_set(deps, info, name),
     }
 }
 
-fn execute_stake_nft(
+fn execute_receive_nft(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
@@ -72,98 +221,120 @@ fn execute_stake_nft(
 ) -> Result<Response, ContractError> {
     let state = CONFIG.load(deps.storage)?;
 
-    let token = TOKENINFO.may_load(deps.storage, &rcv_msg.token_id.clone())?;
+    match state.receipt_address.clone() {
+        Some(receipt_address) if info.sender == receipt_address => {
+            receipts::execute_redeem_receipt(deps, env, state, &receipt_address, rcv_msg)
+        }
+        // stakers may attach anything, only a `ReceiveNftMsg` makes a prize
+        _ => match from_json(&rcv_msg.msg) {
+            Ok(ReceiveNftMsg::RafflePrize { raffle_id }) => {
+                let prize = RafflePrize::Cw721 { address: info.sender.to_string(), token_id: rcv_msg.token_id };
+                raffle::add_prize(deps.storage, &rcv_msg.sender, raffle_id, prize)
+            }
+            Err(_) => execute_stake_nft(deps, env, info, state, rcv_msg),
+        },
+    }
+}
+
+fn execute_stake_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    rcv_msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let token = TOKENINFO.may_load(deps.storage, &rcv_msg.token_id)?;
 
-    if state.can_stake == false{
-        return Err(ContractError::CanNotStake{})
+    if !state.can_stake {
+        return Err(ContractError::CanNotStake {});
     }
-    
-    if info.sender.to_string() != state.nft_address{
-        return Err(ContractError::WrongNftContract {  })
+
+    if info.sender != state.nft_address {
+        return Err(ContractError::WrongNftContract {});
     }
 
-    if token != None {
-        return Err(ContractError::AlreadyStaked {  });
+    if token.is_some() {
+        return Err(ContractError::AlreadyStaked {});
     }
-   
-    CONFIG.update(deps.storage,
-        |mut state|->StdResult<_>{
-            state.total_staked = state.total_staked + Uint128::new(1) ;
-            Ok(state)
-        }
-    )?;
 
-    let token_info = TokenInfo{
-        owner:rcv_msg.sender.clone(),
-        token_id:rcv_msg.token_id.clone(),
-        status : "Staked".to_string(),
-        unstake_time : 0,
-        stake_time :env.block.time.seconds(),
+    CONFIG.update(deps.storage, |mut state| -> Result<_, ContractError> {
+        state.total_staked = state.total_staked.checked_add(Uint128::new(1))?;
+        Ok(state)
+    })?;
+
+    let token_info = TokenInfo {
+        owner: rcv_msg.sender.clone(),
+        token_id: rcv_msg.token_id.clone(),
+        status: "Staked".to_string(),
+        unstake_time: 0,
+        stake_time: env.block.time.seconds(),
         reward_juno: Uint128::new(0),
     };
 
-    let my_nfts = OWNEDTOKEN.may_load(deps.storage,&rcv_msg.sender.clone().to_string())?;
-
-    if my_nfts == None{
-        let mut token_ids:Vec<String> = vec![];
+    OWNEDTOKEN.update(deps.storage, &rcv_msg.sender, |my_nfts| -> StdResult<_> {
+        let mut token_ids = my_nfts.unwrap_or_default();
         token_ids.push(rcv_msg.token_id.clone());
-        OWNEDTOKEN.save(deps.storage,&rcv_msg.sender,&token_ids)?;
+        Ok(token_ids)
+    })?;
 
-    }
+    TOKENINFO.save(deps.storage, &rcv_msg.token_id, &token_info)?;
+    add_staked(deps.storage, &rcv_msg.sender, &rcv_msg.token_id, &env.block)?;
+    sets::refresh(deps.storage, &deps.querier, &rcv_msg.sender)?;
 
-    else{
-        let mut token_ids = my_nfts.unwrap();
-        token_ids.push(rcv_msg.token_id.clone());
-        OWNEDTOKEN.update(deps.storage,&rcv_msg.sender,
-        |my_nfts|->StdResult<_>{
-            Ok(token_ids)
-        }
-    )?;
+    let mut response = Response::default();
+    if let Some(receipt_address) = &state.receipt_address {
+        response = response.add_message(receipts::mint_receipt(receipt_address, &rcv_msg.token_id, &rcv_msg.sender)?);
     }
-
-    TOKENINFO.save(deps.storage, &rcv_msg.token_id.clone(), &token_info)?;
-    
-    Ok(Response::default())
-
+    Ok(response.add_messages(hooks::stake_changed_hooks(deps.storage, StakeChangedHookMsg {
+        owner: rcv_msg.sender,
+        token_id: rcv_msg.token_id,
+        old_status: None,
+        new_status: Some("Staked".to_string()),
+    })?))
 }
 
-
-
 fn execute_unstake_nft(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     token_id: String,
-    
 ) -> Result<Response, ContractError> {
-    // let state = CONFIG.load(deps.storage)?;
+    let state = CONFIG.load(deps.storage)?;
 
-    let token = TOKENINFO.may_load(deps.storage, &token_id)?;
+    let mut token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+        Some(token) => token,
+        None => return Err(ContractError::NotStaked {}),
+    };
+    let moved = receipts::follow_receipt(deps.storage, &deps.querier, &env, &state, &mut token)?;
 
-    if token == None {
-        return Err(ContractError::NotStaked {  });
+    if info.sender != token.owner {
+        return Err(ContractError::Unauthorized {});
     }
-   
-   else {
-      let  token = token.unwrap();    
 
-      if token.owner != info.sender.to_string(){
-          return Err(ContractError::Unauthorized {  })
-      }
+    if token.status != "Staked" {
+        return Err(ContractError::StatusError {});
+    }
 
-      TOKENINFO.update(deps.storage,&token_id,
-        |token_info|->StdResult<_>{
-            let mut token_info = token_info.unwrap();
-            token_info.status = "Unstaking".to_string();
-            token_info.unstake_time = env.block.time.seconds();
-            
-            Ok(token_info)
-        }
-    )?;
-   }
-    
-    Ok(Response::default())
+    TOKENINFO.update(deps.storage, &token_id, |token_info| -> StdResult<_> {
+        let mut token_info = token_info.unwrap();
+        token_info.status = "Unstaking".to_string();
+        token_info.unstake_time = env.block.time.seconds();
+        Ok(token_info)
+    })?;
 
+    CONFIG.update(deps.storage, |mut state| -> Result<_, ContractError> {
+        state.total_staked = state.total_staked.checked_sub(Uint128::new(1))?;
+        Ok(state)
+    })?;
+    remove_staked(deps.storage, &token.owner, &token_id, &env.block)?;
+    sets::refresh(deps.storage, &deps.querier, &token.owner)?;
+
+    Ok(Response::new().add_messages(moved).add_messages(hooks::stake_changed_hooks(deps.storage, StakeChangedHookMsg {
+        owner: token.owner,
+        token_id,
+        old_status: Some("Staked".to_string()),
+        new_status: Some("Unstaking".to_string()),
+    })?))
 }
 
 fn execute_withdraw_nft(
@@ -171,453 +342,823 @@ fn execute_withdraw_nft(
     env: Env,
     info: MessageInfo,
     token_id: String,
-    
 ) -> Result<Response, ContractError> {
     let state = CONFIG.load(deps.storage)?;
 
-    let token = TOKENINFO.may_load(deps.storage, &token_id)?;
+    // with receipts the position is closed by sending the receipt back
+    if state.receipt_address.is_some() {
+        return Err(ContractError::ReceiptRequired {});
+    }
 
-    let mut messages:Vec<CosmosMsg> = vec![];
-    
-    if token == None {
-        return Err(ContractError::NotStaked {  });
+    let token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+        Some(token) => token,
+        None => return Err(ContractError::NotStaked {}),
+    };
+
+    if info.sender != token.owner {
+        return Err(ContractError::Unauthorized {});
     }
-   
-   else {
-      let  token = token.unwrap();
 
-       if token.owner != info.sender.to_string(){
-          return Err(ContractError::Unauthorized {  })
-      }
+    withdraw_position(deps, env, &state, token)
+}
 
-      if token.status =="Staked".to_string(){
-          return Err(ContractError::StatusError {  })
-      }
+/// Pays out the rewards of `token` and sends the NFT back to its owner once
+/// the unstaking period is over.
+pub(crate) fn withdraw_position(
+    deps: DepsMut,
+    env: Env,
+    state: &State,
+    token: TokenInfo,
+) -> Result<Response, ContractError> {
+    let mut messages: Vec<CosmosMsg> = vec![];
 
-      if (env.block.time.seconds() - token.unstake_time)<state.staking_period{
-           return Err(ContractError::TimeRemaining {  })
-      
-        }
+    if token.status == "Staked" {
+        return Err(ContractError::StatusError {});
+    }
 
-       
-      if token.reward_juno > Uint128::new(0){
-      messages.push(CosmosMsg::Bank(BankMsg::Send {
-                to_address: token.owner,
-                amount:vec![Coin{
-                    denom:state.denom.clone(),
-                    amount:token.reward_juno
-                }]
-        }));
+    if checked_sub(env.block.time.seconds(), token.unstake_time)? < state.staking_period {
+        return Err(ContractError::TimeRemaining {});
     }
-    
-    TOKENINFO.remove(deps.storage,&token_id);
-    
-   }
-
-   let my_nfts = OWNEDTOKEN.load(deps.storage,&info.sender.to_string())?;
-   let mut new_nfts:Vec<String> = vec![];
-   for id  in my_nfts{
-     if id !=  token_id{
-         new_nfts.push(id)
-     }
-   }   
-
-   OWNEDTOKEN.save(deps.storage,&info.sender.to_string(),&new_nfts)?;
-    CONFIG.update(deps.storage,
-            |mut state|->StdResult<_>{
-                state.total_staked = state.total_staked-Uint128::new(1);
-                Ok(state)
-            })?;
-  
-   Ok(Response::new()
+
+    let now = env.block.time.seconds();
+    let (payout, penalty) = take_penalty(deps.storage, state, &token, now, &mut messages)?;
+
+    if payout > Uint128::new(0) {
+        let settled = vesting::settle_claim(deps.storage, state, &token.owner, payout, false, now)?;
+        messages.splice(0..0, settled);
+    }
+
+    TOKENINFO.remove(deps.storage, &token.token_id);
+    expiry::clear_credits(deps.storage, &token.token_id);
+    sets::clear_traits(deps.storage, &token.token_id);
+
+    let my_nfts = OWNEDTOKEN.load(deps.storage, &token.owner)?;
+    let new_nfts: Vec<String> = my_nfts.into_iter().filter(|id| *id != token.token_id).collect();
+    OWNEDTOKEN.save(deps.storage, &token.owner, &new_nfts)?;
+
+    messages.extend(hooks::stake_changed_hooks(deps.storage, StakeChangedHookMsg {
+        owner: token.owner.clone(),
+        token_id: token.token_id.clone(),
+        old_status: Some(token.status),
+        new_status: None,
+    })?);
+
+    Ok(Response::new()
         .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
-             contract_addr: state.nft_address, 
-             msg: to_binary(&Cw721ExecuteMsg::TransferNft {
-                  recipient: info.sender.to_string(), 
-                  token_id: token_id })? , 
-             funds: vec![] }))
+            contract_addr: state.nft_address.clone(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: token.owner,
+                token_id: token.token_id
+            })?,
+            funds: vec![]
+        }))
         .add_messages(messages)
-         
-)
+        .add_attribute("penalty", penalty))
 }
 
+pub(crate) fn reward_payout(state: &State, to_address: &str, amount: Uint128) -> CosmosMsg {
+    CosmosMsg::Bank(BankMsg::Send {
+        to_address: to_address.to_string(),
+        amount: vec![Coin {
+            denom: state.denom.clone(),
+            amount
+        }]
+    })
+}
 
+/// Share of the unclaimed reward a token forfeits when it is paid out before
+/// `min_stake_duration` has passed since it was staked.
+fn compute_penalty(
+    policy: Option<&PenaltyPolicy>,
+    token_info: &TokenInfo,
+    now: u64,
+) -> Result<Uint128, OverflowError> {
+    match policy {
+        Some(policy) if now < checked_add(token_info.stake_time, policy.min_stake_duration)? => {
+            checked_multiply_ratio(token_info.reward_juno, policy.percent, 100u64)
+        }
+        _ => Ok(Uint128::zero()),
+    }
+}
 
-fn execute_get_reward(
+/// Takes the early-exit penalty off the unclaimed reward of `token` and routes
+/// it under the policy. Returns what is left for the owner and the penalty.
+/// Every path that pays out `reward_juno` goes through here, so claiming or
+/// transferring first does not dodge the penalty.
+fn take_penalty(
+    storage: &mut dyn Storage,
+    state: &State,
+    token: &TokenInfo,
+    now: u64,
+    messages: &mut Vec<CosmosMsg>,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let policy = PENALTY_POLICY.may_load(storage)?;
+    let penalty = compute_penalty(policy.as_ref(), token, now)?;
+    let payout = token.reward_juno.checked_sub(penalty)?;
+
+    if penalty > Uint128::new(0) {
+        match policy.map(|policy| policy.destination) {
+            Some(PenaltyDestination::Treasury { address }) => {
+                messages.push(reward_payout(state, &address, penalty));
+            }
+            // forfeited rewards join the dust shared out by the next distribution
+            _ => {
+                CONFIG.update(storage, |mut state| -> Result<_, ContractError> {
+                    state.reward_dust = state.reward_dust.checked_add(penalty)?;
+                    Ok(state)
+                })?;
+            }
+        }
+    }
+    Ok((payout, penalty))
+}
+
+fn execute_transfer_stake(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    token_ids: Vec<String>,
-    
+    token_id: String,
+    recipient: String,
 ) -> Result<Response, ContractError> {
     let state = CONFIG.load(deps.storage)?;
 
-    let mut messages:Vec<CosmosMsg> = vec![];
+    if state.receipt_address.is_some() {
+        return Err(ContractError::TransferReceipt {});
+    }
+
+    let recipient = validate_address(deps.api, "recipient", &recipient)?;
+    let mut token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+        Some(token) => token,
+        None => return Err(ContractError::NotStaked {}),
+    };
+
+    if info.sender != token.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if recipient == token.owner {
+        return Err(ContractError::TransferToOwner {});
+    }
 
-    for token_id in token_ids{
-    let token = TOKENINFO.may_load(deps.storage, &token_id)?;
-    if token == None {
-        return Err(ContractError::NotStaked {  });
+    if token.status != "Staked" && !state.transfer_unstaking {
+        return Err(ContractError::TransferWhileUnstaking {});
     }
-   
-   else {
-      let  token = token.unwrap();
 
-       if token.owner != info.sender.to_string(){
-          return Err(ContractError::Unauthorized {  })
-      }
+    // rewards accrued so far are settled with the previous owner
+    let now = env.block.time.seconds();
+    let mut messages = vec![];
+    let (settled, penalty) = take_penalty(deps.storage, &state, &token, now, &mut messages)?;
+    if settled > Uint128::new(0) {
+        let payout = vesting::settle_claim(deps.storage, &state, &token.owner, settled, false, now)?;
+        messages.splice(0..0, payout);
+    }
+    token.reward_juno = Uint128::new(0);
+    TOKENINFO.save(deps.storage, &token_id, &token)?;
+    expiry::clear_credits(deps.storage, &token_id);
+    messages.extend(move_position(deps.storage, &deps.querier, &mut token, &recipient, &env.block)?);
 
-       
-      if token.reward_juno > Uint128::new(0){
-      messages.push(CosmosMsg::Bank(BankMsg::Send {
-                to_address: token.owner,
-                amount:vec![Coin{
-                    denom:state.denom.clone(),
-                    amount:token.reward_juno
-                }]
-        }));
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "transfer_stake")
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipient", recipient)
+        .add_attribute("settled", settled)
+        .add_attribute("penalty", penalty))
+}
+
+/// Moves `token` and its `OWNEDTOKEN` entry to `new_owner`, along with its
+/// voting power and set membership if it is still staked. Returns the hook
+/// messages telling that it left the old owner and arrived with the new one.
+pub(crate) fn move_position(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    token: &mut TokenInfo,
+    new_owner: &str,
+    block: &BlockInfo,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    if token.owner == new_owner {
+        return Ok(vec![]);
+    }
+    let staked = token.status == "Staked";
+    if staked {
+        remove_staked(storage, &token.owner, &token.token_id, block)?;
+        add_staked(storage, new_owner, &token.token_id, block)?;
+    }
+    OWNEDTOKEN.update(storage, &token.owner, |my_nfts| -> StdResult<_> {
+        Ok(my_nfts.unwrap_or_default().into_iter().filter(|id| *id != token.token_id).collect())
+    })?;
+    OWNEDTOKEN.update(storage, new_owner, |my_nfts| -> StdResult<_> {
+        let mut token_ids = my_nfts.unwrap_or_default();
+        token_ids.push(token.token_id.clone());
+        Ok(token_ids)
+    })?;
+    let old_owner = std::mem::replace(&mut token.owner, new_owner.to_string());
+    TOKENINFO.save(storage, &token.token_id, token)?;
+    if staked {
+        sets::refresh(storage, querier, &old_owner)?;
+        sets::refresh(storage, querier, new_owner)?;
     }
-      TOKENINFO.update(deps.storage,&token_id,
-        |token_info|->StdResult<_>{
+
+    let mut messages = hooks::stake_changed_hooks(storage, StakeChangedHookMsg {
+        owner: old_owner,
+        token_id: token.token_id.clone(),
+        old_status: Some(token.status.clone()),
+        new_status: None,
+    })?;
+    messages.extend(hooks::stake_changed_hooks(storage, StakeChangedHookMsg {
+        owner: new_owner.to_string(),
+        token_id: token.token_id.clone(),
+        old_status: None,
+        new_status: Some(token.status.clone()),
+    })?);
+    Ok(messages)
+}
+
+/// Everything that is counted per "Staked" token of an owner, called when a
+/// token of `owner` enters that status or arrives in it.
+fn add_staked(storage: &mut dyn Storage, owner: &str, token_id: &str, block: &BlockInfo) -> Result<(), ContractError> {
+    snapshot::start_interval(storage, owner, token_id, block.time.seconds())?;
+    points::accrue(storage, owner, block.time.seconds())?;
+    voting::add_power(storage, owner, block.height)
+}
+
+fn remove_staked(storage: &mut dyn Storage, owner: &str, token_id: &str, block: &BlockInfo) -> Result<(), ContractError> {
+    snapshot::end_interval(storage, owner, token_id, block.time.seconds())?;
+    points::accrue(storage, owner, block.time.seconds())?;
+    voting::remove_power(storage, owner, block.height)
+}
+
+fn execute_get_reward(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Vec<String>,
+    instant: bool,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    let mut claimed = Uint128::new(0);
+    let mut forfeited = Uint128::new(0);
+    let mut messages = vec![];
+
+    for token_id in token_ids {
+        let mut token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+            Some(token) => token,
+            None => return Err(ContractError::NotStaked {}),
+        };
+        messages.extend(receipts::follow_receipt(deps.storage, &deps.querier, &env, &state, &mut token)?);
+
+        if info.sender != token.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let (payout, penalty) = take_penalty(deps.storage, &state, &token, env.block.time.seconds(), &mut messages)?;
+        claimed = claimed.checked_add(payout)?;
+        forfeited = forfeited.checked_add(penalty)?;
+        TOKENINFO.update(deps.storage, &token_id, |token_info| -> StdResult<_> {
             let mut token_info = token_info.unwrap();
             token_info.reward_juno = Uint128::new(0);
             Ok(token_info)
         })?;
-   }
-}
-   
-   Ok(Response::new()
+        expiry::clear_credits(deps.storage, &token_id);
+    }
+
+    if claimed.is_zero() {
+        return Ok(Response::new().add_messages(messages).add_attribute("penalty", forfeited));
+    }
+    let payout = vesting::settle_claim(
+        deps.storage,
+        &state,
+        info.sender.as_str(),
+        claimed,
+        instant,
+        env.block.time.seconds(),
+    )?;
+    Ok(Response::new()
+        .add_messages(payout)
         .add_messages(messages)
-         
-)
+        .add_attribute("claimed", claimed)
+        .add_attribute("penalty", forfeited))
+}
+
+/// A token shares in a distribution once it has been staked for
+/// `min_stake_age`, and keeps sharing during the unbonding cooldown until
+/// `staking_period` has elapsed.
+fn is_reward_eligible(token_info: &TokenInfo, state: &State, now: u64) -> Result<bool, OverflowError> {
+    if now < reward_eligible_at(token_info, state)? {
+        return Ok(false);
+    }
+    Ok(token_info.status == "Staked"
+        || (token_info.status == "Unstaking"
+            && checked_sub(now, token_info.unstake_time)? < state.staking_period))
+}
+
+fn reward_eligible_at(token_info: &TokenInfo, state: &State) -> Result<u64, OverflowError> {
+    checked_add(token_info.stake_time, state.min_stake_age)
+}
+
+fn eligible_token_ids(storage: &dyn Storage, state: &State, now: u64) -> Result<Vec<String>, ContractError> {
+    let mut eligible = vec![];
+    for item in TOKENINFO.range(storage, None, None, Order::Ascending) {
+        let (token_id, token_info) = item?;
+        if is_reward_eligible(&token_info, state, now)? {
+            eligible.push(token_id);
+        }
+    }
+    Ok(eligible)
 }
 
 fn execute_distribute_reward(
     deps: DepsMut,
-    env:  Env,
+    env: Env,
     info: MessageInfo
-)->Result<Response,ContractError>{
-
+) -> Result<Response, ContractError> {
     let state = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
 
-    if info.sender.to_string() != state.reward_wallet{
+    if info.sender != state.reward_wallet {
         return Err(ContractError::Unauthorized {});
     }
 
-    if (env.block.time.seconds() - state.last_distribute)<state.distribute_period{
-        return Err(ContractError::CanNotDistribute {  })
+    if checked_sub(now, state.last_distribute)? < state.distribute_period {
+        return Err(ContractError::CanNotDistribute {});
     }
 
-    let amount_juno= info
+    let amount_juno = info
         .funds
         .iter()
         .find(|c| c.denom == state.denom)
-        .map(|c| Uint128::from(c.amount))
+        .map(|c| c.amount)
         .unwrap_or_else(Uint128::zero);
-    
-    let token_id :StdResult<Vec<String>>  = TOKENINFO
-        .keys(deps.storage, None, None, Order::Ascending)
-        .collect();
 
-    let token_group = token_id?;
+    let eligible = eligible_token_ids(deps.storage, &state, now)?;
+    let pools = pools::eligible_pools(deps.storage, now)?;
 
-   if token_group.len() == 0 {
-       return Err(ContractError::NotStaked {  })
-   }
+    // the NFT pool and each LP pool get a cut proportional to their weight
+    let nft_weight = if eligible.is_empty() { 0 } else { state.nft_weight };
+    let total_weight = pools
+        .iter()
+        .try_fold(nft_weight, |total, (pool, _)| checked_add(total, pool.weight))?;
+    if total_weight == 0 {
+        return Err(ContractError::NotStaked {});
+    }
 
-   let mut reward_number = state.total_staked;
+    // dust left over from earlier rounds is shared out together with the new funds
+    let distributable = amount_juno.checked_add(state.reward_dust)?;
+    let nft_share = checked_multiply_ratio(distributable, nft_weight, total_weight)?;
 
- 
-    for token_id in token_group.clone(){
-        let token_info = TOKENINFO.load(deps.storage, &token_id )?;
-        if  (env.block.time.seconds() - token_info.unstake_time) >state.staking_period && token_info.status == "Unstaking".to_string() {
-          reward_number = reward_number - Uint128::new(1);   
-        }
-    }
+    // tokens completing a set weigh more than `BASE_WEIGHT` within the NFT pool
+    let set_weights = sets::set_weights(deps.storage)?;
+    let token_weight = |token_id: &String| set_weights.get(token_id).copied().unwrap_or(sets::BASE_WEIGHT);
+    let total_token_weight = eligible
+        .iter()
+        .try_fold(0u64, |total, token_id| checked_add(total, token_weight(token_id)))?;
+    let per_token = if eligible.is_empty() {
+        Uint128::zero()
+    } else {
+        checked_multiply_ratio(nft_share, sets::BASE_WEIGHT, total_token_weight)?
+    };
 
-    if reward_number == Uint128::new(0){
-        return Err(ContractError::NotStaked {  })
+    let mut pool_rewards = Uint128::zero();
+    for (pool, eligible_total) in pools.iter() {
+        let share = checked_multiply_ratio(distributable, pool.weight, total_weight)?;
+        let credited = pools::credit_pool(deps.storage, &pool.id, share, *eligible_total, now)?;
+        pool_rewards = pool_rewards.checked_add(credited)?;
     }
-   
 
-     for token_id in token_group{
-            let token_info = TOKENINFO.load(deps.storage,&token_id)?;
-            if token_info.status == "Staked".to_string() || (token_info.status == "Unstaking".to_string()&&(env.block.time.seconds() - token_info.unstake_time) <state.staking_period)
-            {       TOKENINFO.update(deps.storage, &token_id,
-                |token_info|->StdResult<_>{
-                    let mut token_info = token_info.unwrap();
-                    token_info.reward_juno = token_info.reward_juno + amount_juno/reward_number;
-                    Ok(token_info)
-            }
-            )?; }
+    let epoch_id = checked_add(DISTRIBUTION_COUNT.may_load(deps.storage)?.unwrap_or_default(), 1)?;
+    let mut nft_rewards = Uint128::zero();
+    for token_id in eligible.iter() {
+        let credit = match set_weights.get(token_id) {
+            Some(weight) => checked_multiply_ratio(nft_share, *weight, total_token_weight)?,
+            None => per_token,
+        };
+        TOKENINFO.update(deps.storage, token_id, |token_info| -> Result<_, ContractError> {
+            let mut token_info = token_info.ok_or(ContractError::NotStaked {})?;
+            token_info.reward_juno = token_info.reward_juno.checked_add(credit)?;
+            Ok(token_info)
+        })?;
+        expiry::record_credit(deps.storage, token_id, epoch_id, now, credit)?;
+        nft_rewards = nft_rewards.checked_add(credit)?;
     }
+    let dust = distributable.checked_sub(nft_rewards)?.checked_sub(pool_rewards)?;
 
-    CONFIG.update(deps.storage,
-        |mut state|-> StdResult<_>{
-            state.last_distribute = env.block.time.seconds();
-            Ok(state)
-        }    
-    )?;
-
-    Ok(Response::default())
-}
-
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.last_distribute = now;
+        state.reward_dust = dust;
+        Ok(state)
+    })?;
 
+    DISTRIBUTION_COUNT.save(deps.storage, &epoch_id)?;
+    DISTRIBUTIONS.save(deps.storage, epoch_id, &DistributionEpoch {
+        id: epoch_id,
+        time: now,
+        amount: amount_juno,
+        denom: state.denom,
+        eligible_count: eligible.len() as u64,
+        pool_rewards,
+        carried_dust: state.reward_dust,
+        per_token,
+        dust,
+    })?;
 
-fn execute_reward_wallet(
-    deps: DepsMut,
-    _env : Env,
-    info: MessageInfo,
-    address: String,
-)->Result<Response,ContractError>{
+    Ok(Response::new()
+        .add_attribute("action", "distribute_reward")
+        .add_attribute("epoch", epoch_id.to_string())
+        .add_attribute("amount", amount_juno)
+        .add_attribute("eligible_count", eligible.len().to_string()))
+}
 
-    let state = CONFIG.load(deps.storage)?;
+/// Validates `address` and returns it in its normalized form.
+pub(crate) fn validate_address(api: &dyn Api, field: &str, address: &str) -> Result<String, ContractError> {
+    api.addr_validate(address)
+        .map(|addr| addr.to_string())
+        .map_err(|_| ContractError::InvalidAddress {
+            field: field.to_string(),
+            address: address.to_string(),
+        })
+}
 
-    if info.sender.to_string() != state.owner{
-        return Err(ContractError::Unauthorized {});
+/// Native denoms follow the bank module format: a letter followed by
+/// 2 to 127 letters, digits or one of `/:._-`.
+pub(crate) fn validate_denom(denom: &str) -> Result<(), ContractError> {
+    let mut chars = denom.chars();
+    let valid = denom.len() >= 3
+        && denom.len() <= 128
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c));
+    if !valid {
+        return Err(ContractError::InvalidDenom { denom: denom.to_string() });
     }
-    CONFIG.update(deps.storage,
-    |mut state|->StdResult<_>{
-        state.reward_wallet = address;
-        Ok(state)
-    })?;
-    Ok(Response::default())
+    Ok(())
 }
 
-
-fn execute_nft_address(
-    deps: DepsMut,
-    _env:Env,
-    info: MessageInfo,
-    address: String,
-) -> Result<Response, ContractError> {
-    let mut state = CONFIG.load(deps.storage)?;
-    deps.api.addr_validate(&address)?;
-    state.nft_address = address;
-    
-    if state.owner != info.sender.to_string() {
-        return Err(ContractError::Unauthorized {});
+pub(crate) fn validate_period(field: &str, value: u64, min: u64) -> Result<u64, ContractError> {
+    if value < min || value > MAX_PERIOD {
+        return Err(ContractError::InvalidPeriod {
+            field: field.to_string(),
+            value,
+            min,
+            max: MAX_PERIOD,
+        });
     }
-
-    CONFIG.save(deps.storage, &state)?;
-    Ok(Response::default())
+    Ok(value)
 }
 
-fn execute_token_address(
+/// Applies every field that is set in `update` and emits an attribute for
+/// each of them.
+fn execute_update_config(
     deps: DepsMut,
-    _env:Env,
+    env: Env,
     info: MessageInfo,
-    address: String,
+    update: UpdateConfigMsg,
 ) -> Result<Response, ContractError> {
     let mut state = CONFIG.load(deps.storage)?;
-    deps.api.addr_validate(&address)?;
-    state.token_address = address;
-    
-    if state.owner != info.sender.to_string() {
+
+    if info.sender != state.owner {
         return Err(ContractError::Unauthorized {});
     }
 
-    CONFIG.save(deps.storage, &state)?;
-    Ok(Response::default())
-}
-
-
-fn execute_set_owner(
-    deps: DepsMut,
-     _env:Env,
-    info: MessageInfo,
-    address: String,
-) -> Result<Response, ContractError> {
-    let mut state = CONFIG.load(deps.storage)?;
-    deps.api.addr_validate(&address)?;
-    state.owner = address;
-    
-    if state.owner != info.sender.to_string() {
-        return Err(ContractError::Unauthorized {});
+    let mut response = Response::new().add_attribute("action", "update_config");
+    if let Some(owner) = update.owner {
+        state.owner = validate_address(deps.api, "owner", &owner)?;
+        response = response.add_attribute("owner", &state.owner);
+    }
+    if let Some(reward_wallet) = update.reward_wallet {
+        state.reward_wallet = validate_address(deps.api, "reward_wallet", &reward_wallet)?;
+        response = response.add_attribute("reward_wallet", &state.reward_wallet);
+    }
+    if let Some(nft_address) = update.nft_address {
+        state.nft_address = validate_address(deps.api, "nft_address", &nft_address)?;
+        response = response.add_attribute("nft_address", &state.nft_address);
+    }
+    if let Some(token_address) = update.token_address {
+        let token_address = validate_address(deps.api, "token_address", &token_address)?;
+        response = response.add_attribute("token_address", &token_address);
+        state.token_address = Some(token_address);
+    }
+    if let Some(staking_period) = update.staking_period {
+        state.staking_period = validate_period("staking_period", staking_period, 1)?;
+        response = response.add_attribute("staking_period", staking_period.to_string());
+    }
+    if let Some(distribute_period) = update.distribute_period {
+        state.distribute_period = validate_period("distribute_period", distribute_period, 1)?;
+        response = response.add_attribute("distribute_period", distribute_period.to_string());
+    }
+    if let Some(can_stake) = update.can_stake {
+        state.can_stake = can_stake;
+        response = response.add_attribute("can_stake", can_stake.to_string());
+    }
+    if let Some(min_stake_age) = update.min_stake_age {
+        state.min_stake_age = validate_period("min_stake_age", min_stake_age, 0)?;
+        response = response.add_attribute("min_stake_age", min_stake_age.to_string());
+    }
+    if let Some(nft_weight) = update.nft_weight {
+        state.nft_weight = nft_weight;
+        response = response.add_attribute("nft_weight", nft_weight.to_string());
+    }
+    if let Some(transfer_unstaking) = update.transfer_unstaking {
+        state.transfer_unstaking = transfer_unstaking;
+        response = response.add_attribute("transfer_unstaking", transfer_unstaking.to_string());
+    }
+    if let Some(points_rate) = update.points_rate {
+        // points up to now are earned at the old rate
+        points::update_index(deps.storage, env.block.time.seconds())?;
+        state.points_rate = points_rate;
+        response = response.add_attribute("points_rate", points_rate.to_string());
+    }
+    if let Some(reward_expiry) = update.reward_expiry {
+        state.reward_expiry = validate_period("reward_expiry", reward_expiry, 0)?;
+        response = response.add_attribute("reward_expiry", reward_expiry.to_string());
     }
 
     CONFIG.save(deps.storage, &state)?;
-    Ok(Response::default())
+    Ok(response)
 }
 
-
-
-fn execute_staking_period(
+fn execute_set_penalty_policy(
     deps: DepsMut,
-    _env : Env,
     info: MessageInfo,
-    time: u64,
-)->Result<Response,ContractError>{
-
+    policy: Option<PenaltyPolicy>,
+) -> Result<Response, ContractError> {
     let state = CONFIG.load(deps.storage)?;
 
-    if info.sender.to_string() != state.owner{
+    if info.sender != state.owner {
         return Err(ContractError::Unauthorized {});
     }
-    CONFIG.update(deps.storage,
-    |mut state|->StdResult<_>{
-        state.staking_period = time;
-        Ok(state)
-    })?;
-    Ok(Response::default())
-}
-
-fn execute_distribute_period(
-    deps: DepsMut,
-    _env : Env,
-    info: MessageInfo,
-    time: u64,
-)->Result<Response,ContractError>{
 
-    let state = CONFIG.load(deps.storage)?;
-
-    if info.sender.to_string() != state.reward_wallet{
-        return Err(ContractError::Unauthorized {});
+    match policy {
+        Some(policy) => {
+            if policy.percent > 100 {
+                return Err(ContractError::InvalidPenaltyPercent { percent: policy.percent });
+            }
+            validate_period("min_stake_duration", policy.min_stake_duration, 1)?;
+            if let PenaltyDestination::Treasury { address } = &policy.destination {
+                validate_address(deps.api, "treasury", address)?;
+            }
+            PENALTY_POLICY.save(deps.storage, &policy)?;
+        }
+        None => PENALTY_POLICY.remove(deps.storage),
     }
-    CONFIG.update(deps.storage,
-    |mut state|->StdResult<_>{
-        state.distribute_period = time;
-        Ok(state)
-    })?;
     Ok(Response::default())
 }
 
-
-
-fn execute_set_stake(
-    deps: DepsMut,
-
-    info: MessageInfo,
-    flag: bool,
-)->Result<Response,ContractError>{
-
-    let state = CONFIG.load(deps.storage)?;
-
-    if info.sender.to_string() != state.owner{
-        return Err(ContractError::Unauthorized {});
-    }
-    CONFIG.update(deps.storage,
-    |mut state|->StdResult<_>{
-        state.can_stake = flag;
-        Ok(state)
-    })?;
-    Ok(Response::default())
-}
-
-
-
 fn execute_withdraw_all(
     deps: DepsMut,
-    _env : Env,
+    env: Env,
     info: MessageInfo,
     amount_juno: Uint128,
-)->Result<Response,ContractError>{
-
+) -> Result<Response, ContractError> {
     let state = CONFIG.load(deps.storage)?;
 
-    if info.sender.to_string() != state.owner{
+    if info.sender != state.owner {
         return Err(ContractError::Unauthorized {});
     }
-   
+
+    // vesting rewards are owed to stakers and stay in the contract
+    let locked = VESTING_LOCKED.may_load(deps.storage)?.unwrap_or_default();
+    let balance = deps.querier.query_balance(&env.contract.address, &state.denom)?.amount;
+    if balance < locked.checked_add(amount_juno)? {
+        return Err(ContractError::VestingLocked { balance, locked });
+    }
+
     Ok(Response::new()
         .add_message(CosmosMsg::Bank(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount:vec![Coin{
-                    denom:state.denom.clone(),
-                    amount:amount_juno
-                }]
-        }))
-)
+            to_address: info.sender.to_string(),
+            amount: vec![Coin {
+                denom: state.denom.clone(),
+                amount: amount_juno
+            }]
+        })))
 }
 
 
-
-
-
-
-#[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-          QueryMsg::GetStateInfo {  } => to_binary(&query_state_info(deps)?),
-          QueryMsg::GetAllTokens {} => to_binary(&query_get_members(deps)?),
-          QueryMsg::GetTokenInfo {} => to_binary(&query_token_info(deps)?),
-          QueryMsg::GetCurrentTime{} => to_binary(&query_get_current_time(deps,_env)?),
-          QueryMsg::GetToken { token_id } => to_binary(&query_get_token(deps,token_id)?),
-          QueryMsg::GetMyIds { address } => to_binary(&query_my_ids(deps,address)?),
-          QueryMsg::GetMyInfo { address }=> to_binary(&query_my_info(deps,address)?)
+        QueryMsg::GetStateInfo {} => to_json_binary(&query_state_info(deps)?),
+        QueryMsg::GetAllTokens {} => to_json_binary(&query_get_members(deps)?),
+        QueryMsg::GetTokenInfo {} => to_json_binary(&query_token_info(deps)?),
+        QueryMsg::GetCurrentTime {} => to_json_binary(&query_get_current_time(deps, env)?),
+        QueryMsg::GetToken { token_id } => to_json_binary(&query_get_token(deps, token_id)?),
+        QueryMsg::GetMyIds { address } => to_json_binary(&query_my_ids(deps, address)?),
+        QueryMsg::GetMyInfo { address } => to_json_binary(&query_my_info(deps, address)?),
+        QueryMsg::PendingRewards { address } => to_json_binary(&query_pending_rewards(deps, address)?),
+        QueryMsg::PoolStats {} => to_json_binary(&query_pool_stats(deps, env)?),
+        QueryMsg::DistributionHistory { start_after, limit } => {
+            to_json_binary(&query_distribution_history(deps, start_after, limit)?)
+        }
+        QueryMsg::CarriedDust {} => to_json_binary(&query_carried_dust(deps)?),
+        QueryMsg::PenaltyPolicy {} => to_json_binary(&query_penalty_policy(deps)?),
+        QueryMsg::PenaltyPreview { token_id } => to_json_binary(&query_penalty_preview(deps, env, token_id)?),
+        QueryMsg::Pools {} => to_json_binary(&pools::query_pools(deps)?),
+        QueryMsg::Position { pool, address } => to_json_binary(&pools::query_position(deps, pool, address)?),
+        QueryMsg::UnbondingQueue { address } => to_json_binary(&pools::query_unbonding_queue(deps, env, address)?),
+        QueryMsg::VotingPowerAtHeight { address, height } => {
+            to_json_binary(&voting::query_voting_power_at_height(deps, env, address, height)?)
+        }
+        QueryMsg::TotalPowerAtHeight { height } => to_json_binary(&voting::query_total_power_at_height(deps, env, height)?),
+        QueryMsg::Hooks {} => to_json_binary(&hooks::query_hooks(deps)?),
+        QueryMsg::StakerSnapshot { as_of, min_tokens, min_duration, start_after, limit } => to_json_binary(
+            &snapshot::query_staker_snapshot(deps, as_of, min_tokens, min_duration, start_after, limit)?,
+        ),
+        QueryMsg::Points { address } => to_json_binary(&points::query_points(deps, env, address)?),
+        QueryMsg::PointSpends { address, start_after, limit } => {
+            to_json_binary(&points::query_point_spends(deps, address, start_after, limit)?)
+        }
+        QueryMsg::PointSpenders {} => to_json_binary(&points::query_point_spenders(deps)?),
+        QueryMsg::VestingInfo { address } => to_json_binary(&vesting::query_vesting_info(deps, env, address)?),
+        QueryMsg::ExpiringRewards { address } => to_json_binary(&expiry::query_expiring_rewards(deps, env, address)?),
+        QueryMsg::Raffle { raffle_id } => to_json_binary(&raffle::query_raffle(deps, raffle_id)?),
+        QueryMsg::Raffles { start_after, limit } => to_json_binary(&raffle::query_raffles(deps, start_after, limit)?),
+        QueryMsg::CollectionSets {} => to_json_binary(&sets::query_collection_sets(deps)?),
+        QueryMsg::MySets { address } => to_json_binary(&sets::query_my_sets(deps, address)?),
     }
 }
 
-pub fn query_state_info(deps:Deps) -> StdResult<State>{
-    let state =  CONFIG.load(deps.storage)?;
+pub fn query_state_info(deps: Deps) -> StdResult<State> {
+    let state = CONFIG.load(deps.storage)?;
     Ok(state)
 }
 
-pub fn query_get_current_time(deps:Deps,env:Env) -> StdResult<u64>{
-    Ok(env.block.time.seconds())
+pub fn query_get_current_time(_deps: Deps, env: Env) -> StdResult<CurrentTimeResponse> {
+    Ok(CurrentTimeResponse { time: env.block.time.seconds() })
 }
 
-pub fn query_get_members(deps:Deps) -> StdResult<Vec<String>>{
-     let token_id :StdResult<Vec<String>>  = TOKENINFO
+pub fn query_get_members(deps: Deps) -> StdResult<AllTokensResponse> {
+    let tokens = TOKENINFO
         .keys(deps.storage, None, None, Order::Ascending)
-        .collect();
-    Ok(token_id?)
+        .collect::<StdResult<_>>()?;
+    Ok(AllTokensResponse { tokens })
 }
 
-pub fn query_token_info(deps:Deps)->StdResult<Vec<TokenInfo>>{
-      let res: StdResult<Vec<TokenInfo>> = TOKENINFO
+pub fn query_token_info(deps: Deps) -> StdResult<AllTokenInfoResponse> {
+    let tokens = TOKENINFO
         .range(deps.storage, None, None, Order::Ascending)
-        .map(|kv_item|parse_token_info(kv_item))
-        .collect();
-    Ok(res?)
+        .map(parse_token_info)
+        .collect::<StdResult<_>>()?;
+    Ok(AllTokenInfoResponse { tokens })
 }
 
 fn parse_token_info(
-    item: StdResult<(String,TokenInfo)>,
+    item: StdResult<(String, TokenInfo)>,
 ) -> StdResult<TokenInfo> {
-    item.and_then(|(k, token_info)| {
-        Ok(token_info)
+    item.map(|(_, token_info)| token_info)
+}
+
+pub fn query_get_token(deps: Deps, token_id: String) -> StdResult<TokenResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let token_info = TOKENINFO.load(deps.storage, &token_id)?;
+    Ok(TokenResponse {
+        reward_eligible_at: reward_eligible_at(&token_info, &state)?,
+        info: token_info,
     })
 }
 
+pub fn query_my_ids(deps: Deps, address: String) -> StdResult<MyIdsResponse> {
+    let my_ids = OWNEDTOKEN.may_load(deps.storage, &address)?;
+    Ok(MyIdsResponse { token_ids: my_ids.unwrap_or_default() })
+}
 
-pub fn query_get_token(deps:Deps,token_id:String) -> StdResult<TokenInfo>{
-    let token_info = TOKENINFO.load(deps.storage,&token_id)?;
-    Ok(token_info)
+pub fn query_my_info(deps: Deps, address: String) -> StdResult<MyInfoResponse> {
+    let my_ids = OWNEDTOKEN.may_load(deps.storage, &address)?;
+    let tokens = my_ids
+        .unwrap_or_default()
+        .iter()
+        .map(|id| TOKENINFO.load(deps.storage, id))
+        .collect::<StdResult<_>>()?;
+    Ok(MyInfoResponse { tokens })
 }
 
-pub fn query_my_ids(deps:Deps,address:String) -> StdResult<Vec<String>>{
-    let my_ids = OWNEDTOKEN.may_load(deps.storage,&address)?;
-    if my_ids == None{
-        Ok(vec![])
-    }
-    else{
-        Ok(my_ids.unwrap())
-    }
+pub fn query_pending_rewards(deps: Deps, address: String) -> StdResult<PendingRewardsResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let my_nfts = query_my_info(deps, address.clone())?.tokens;
+
+    let total = my_nfts
+        .iter()
+        .try_fold(Uint128::zero(), |total, token_info| total.checked_add(token_info.reward_juno))?
+        .checked_add(pools::pending_pool_rewards(deps.storage, &address)?)?;
+
+    Ok(PendingRewardsResponse {
+        address,
+        token_count: my_nfts.len() as u64,
+        rewards: vec![Coin {
+            denom: state.denom,
+            amount: total
+        }]
+    })
 }
 
-pub fn query_my_info(deps:Deps,address:String) -> StdResult<Vec<TokenInfo>>{
-    let my_ids = OWNEDTOKEN.may_load(deps.storage,&address)?;
-    if my_ids == None{
-        Ok(vec![])
-    }
-    else{
-        let mut my_nfts:Vec<TokenInfo> = vec![];
-        for id in my_ids.unwrap(){            
-            let token_info = TOKENINFO.load(deps.storage, &id)?;
-            my_nfts.push(token_info);         
+pub fn query_pool_stats(deps: Deps, env: Env) -> StdResult<PoolStatsResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let eligible = eligible_token_ids(deps.storage, &state, env.block.time.seconds())
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let mut recent: Vec<DistributionEpoch> = DISTRIBUTIONS
+        .range(deps.storage, None, None, Order::Descending)
+        .take(RECENT_DISTRIBUTION_WINDOW)
+        .map(|item| item.map(|(_, epoch)| epoch))
+        .collect::<StdResult<_>>()?;
+    recent.reverse();
+
+    Ok(PoolStatsResponse {
+        total_staked: state.total_staked,
+        eligible_count: eligible.len() as u64,
+        last_distribute: state.last_distribute,
+        last_distribute_amount: recent.last().map(|epoch| epoch.amount).unwrap_or_default(),
+        denom: state.denom.clone(),
+        annual_reward_per_nft: estimate_annual_reward_per_nft(&recent, state.distribute_period)?,
+    })
+}
+
+pub fn query_carried_dust(deps: Deps) -> StdResult<CarriedDustResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    Ok(CarriedDustResponse {
+        amount: Coin {
+            denom: state.denom,
+            amount: state.reward_dust
+        }
+    })
+}
+
+pub fn query_penalty_policy(deps: Deps) -> StdResult<PenaltyPolicyResponse> {
+    Ok(PenaltyPolicyResponse { policy: PENALTY_POLICY.may_load(deps.storage)? })
+}
+
+pub fn query_penalty_preview(deps: Deps, env: Env, token_id: String) -> StdResult<PenaltyPreviewResponse> {
+    let token_info = TOKENINFO.load(deps.storage, &token_id)?;
+    let policy = PENALTY_POLICY.may_load(deps.storage)?;
+    let penalty = compute_penalty(policy.as_ref(), &token_info, env.block.time.seconds())?;
+
+    Ok(PenaltyPreviewResponse {
+        token_id,
+        penalty,
+        payout: token_info.reward_juno.checked_sub(penalty)?,
+        penalty_free_at: match policy {
+            Some(policy) => checked_add(token_info.stake_time, policy.min_stake_duration)?,
+            None => token_info.stake_time,
+        },
+    })
+}
+
+pub fn query_distribution_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<DistributionHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_HISTORY_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+
+    let distributions = DISTRIBUTIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, epoch)| epoch))
+        .collect::<StdResult<_>>()?;
+
+    Ok(DistributionHistoryResponse { distributions })
+}
+
+/// Extrapolates the per-token reward of the recent distributions to a year.
+/// With a single sample the configured `distribute_period` stands in for the
+/// observed interval between distributions.
+fn estimate_annual_reward_per_nft(
+    epochs: &[DistributionEpoch],
+    distribute_period: u64,
+) -> Result<Uint128, OverflowError> {
+    match epochs {
+        [] => Ok(Uint128::zero()),
+        [only] => {
+            if distribute_period == 0 {
+                return Ok(Uint128::zero());
+            }
+            checked_multiply_ratio(only.per_token, SECONDS_PER_YEAR, distribute_period)
+        }
+        [first, .., last] => {
+            let span = checked_sub(last.time, first.time)?;
+            if span == 0 {
+                return Ok(Uint128::zero());
+            }
+            let earned = epochs[1..]
+                .iter()
+                .try_fold(Uint128::zero(), |total, epoch| total.checked_add(epoch.per_token))?;
+            checked_multiply_ratio(earned, SECONDS_PER_YEAR, span)
         }
-          Ok(my_nfts)
     }
 }
 
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{CosmosMsg};
+    use cosmwasm_std::{attr, CosmosMsg};
+    use crate::state::{PoolAsset, VestingTranche};
+
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
 
     #[test]
     fn testing() {
@@ -626,7 +1167,17 @@ mod tests {
             denom : "ujuno".to_string(),
             staking_period : 1000,
             reward_wallet :"reward_wallet".to_string(),
-            distribute_period:100
+            distribute_period:100,
+            owner:None,
+            nft_address:"nft_address".to_string(),
+            token_address:None,
+            can_stake:true,
+            min_stake_age:None,
+            nft_weight:None,
+            transfer_unstaking:None,
+            points_rate:None,
+            reward_expiry:None,
+            receipts:None
         };
         let info = mock_info("creator", &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
@@ -636,7 +1187,7 @@ mod tests {
         let state = query_state_info(deps.as_ref()).unwrap();
         assert_eq!(state,State{
             nft_address : "nft_address".to_string(),
-            token_address : "token_address".to_string(),
+            token_address : None,
             owner:"creator".to_string(),
             staking_period : 1000,
             denom : "ujuno".to_string(),
@@ -644,29 +1195,40 @@ mod tests {
             total_staked:Uint128::new(0),
             can_stake : true,
             last_distribute : mock_env().block.time.seconds(),
-            distribute_period:100
+            distribute_period:100,
+            reward_dust:Uint128::new(0),
+            min_stake_age:0,
+            nft_weight:100,
+            transfer_unstaking:false,
+            points_rate:0,
+            reward_expiry:0,
+            receipt_address:None
         });
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SetNftAddress { address:"nft_address1".to_string() };
-        execute(deps.as_mut(),mock_env(),info,msg).unwrap();
-
-        let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SetTokenAddress { address:"token_address1".to_string() };
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+            nft_address:Some("nft_address1".to_string()),
+            token_address:Some("token_address1".to_string()),
+            ..UpdateConfigMsg::default()
+        });
         execute(deps.as_mut(),mock_env(),info,msg).unwrap();
 
         let state = query_state_info(deps.as_ref()).unwrap();
         assert_eq!(state.nft_address,"nft_address1".to_string());
-        assert_eq!(state.token_address, "token_address1".to_string());
+        assert_eq!(state.token_address, Some("token_address1".to_string()));
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SetRewardWallet { address:"reward_wallet1".to_string() };
-        execute(deps.as_mut(),mock_env(),info,msg).unwrap();
-
-        
-        let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SetDistributePeriod { time:150 };
-        execute(deps.as_mut(),mock_env(),info,msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+            reward_wallet:Some("reward_wallet1".to_string()),
+            distribute_period:Some(150),
+            ..UpdateConfigMsg::default()
+        });
+        let res = execute(deps.as_mut(),mock_env(),info,msg).unwrap();
+        assert_eq!(res.attributes, vec![
+            attr("action", "update_config"),
+            attr("reward_wallet", "reward_wallet1"),
+            attr("distribute_period", "150"),
+        ]);
 
         let state= query_state_info(deps.as_ref()).unwrap();
         assert_eq!(state.distribute_period,150);
@@ -676,7 +1238,7 @@ mod tests {
        
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SetStakingPeriod { time: 1200 };
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { staking_period:Some(1200), ..UpdateConfigMsg::default() });
         execute(deps.as_mut(),mock_env(),info,msg).unwrap();
         
         let state = query_state_info(deps.as_ref()).unwrap();
@@ -686,7 +1248,7 @@ mod tests {
         let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg{
             sender:"owner1".to_string(),
             token_id : "reveal1".to_string(),
-            msg : to_binary(&"abc".to_string()).unwrap()
+            msg : to_json_binary(&"abc".to_string()).unwrap()
         });
         execute(deps.as_mut(),mock_env(),info,msg).unwrap();
 
@@ -694,27 +1256,27 @@ mod tests {
         let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg{
             sender:"owner1".to_string(),
             token_id : "reveal2".to_string(),
-            msg : to_binary(&"abc".to_string()).unwrap()
+            msg : to_json_binary(&"abc".to_string()).unwrap()
         });
         execute(deps.as_mut(),mock_env(),info,msg).unwrap();
 
         // let my_nfs = query_my_ids(deps,"")
 
-        let tokens = query_get_members(deps.as_ref()).unwrap();
+        let tokens = query_get_members(deps.as_ref()).unwrap().tokens;
         assert_eq!(tokens,vec!["reveal1","reveal2"]);
 
-        let my_ids = query_my_ids(deps.as_ref(), "owner2".to_string()).unwrap();
+        let my_ids = query_my_ids(deps.as_ref(), "owner2".to_string()).unwrap().token_ids;
         let eq_my_ids:Vec<String> = vec![];
         assert_eq!(my_ids,eq_my_ids);
 
-        let my_ids = query_my_ids(deps.as_ref(), "owner1".to_string()).unwrap();
+        let my_ids = query_my_ids(deps.as_ref(), "owner1".to_string()).unwrap().token_ids;
         assert_eq!(my_ids,["reveal1","reveal2"]);
 
-        let my_token_infos = query_my_info(deps.as_ref(),"owner2".to_string()).unwrap();
+        let my_token_infos = query_my_info(deps.as_ref(),"owner2".to_string()).unwrap().tokens;
         let eq_my_ids:Vec<TokenInfo> = vec![];
         assert_eq!(my_token_infos,eq_my_ids);
 
-        let my_token_infos = query_my_info(deps.as_ref(),"owner1".to_string()).unwrap();
+        let my_token_infos = query_my_info(deps.as_ref(),"owner1".to_string()).unwrap().tokens;
         assert_eq!(my_token_infos,vec![TokenInfo{
             owner:"owner1".to_string(),
             token_id:"reveal1".to_string(),
@@ -741,10 +1303,10 @@ mod tests {
 
         assert_eq!(state.total_staked,Uint128::new(1));
 
-        let tokens = query_get_members(deps.as_ref()).unwrap();
+        let tokens = query_get_members(deps.as_ref()).unwrap().tokens;
         assert_eq!(tokens,vec!["reveal1","reveal2"]);
 
-        let token_infos = query_token_info(deps.as_ref()).unwrap();
+        let token_infos = query_token_info(deps.as_ref()).unwrap().tokens;
         assert_eq!(token_infos,vec![TokenInfo{
             owner:"owner1".to_string(),
             token_id:"reveal1".to_string(),
@@ -761,7 +1323,7 @@ mod tests {
             unstake_time :0
         }]);
 
-         let my_token_infos = query_my_info(deps.as_ref(),"owner1".to_string()).unwrap();
+         let my_token_infos = query_my_info(deps.as_ref(),"owner1".to_string()).unwrap().tokens;
         assert_eq!(my_token_infos,vec![TokenInfo{
             owner:"owner1".to_string(),
             token_id:"reveal1".to_string(),
@@ -781,9 +1343,9 @@ mod tests {
 
         let info = mock_info("reward_wallet1", &[]);     
         let msg = ExecuteMsg::DistributeReward {   };
-        execute(deps.as_mut(),mock_env(),info,msg).unwrap();
+        execute(deps.as_mut(),later(150),info,msg).unwrap();
 
-        let token_infos = query_token_info(deps.as_ref()).unwrap();
+        let token_infos = query_token_info(deps.as_ref()).unwrap().tokens;
         assert_eq!(token_infos,vec![TokenInfo{
             owner:"owner1".to_string(),
             token_id:"reveal1".to_string(),
@@ -801,7 +1363,7 @@ mod tests {
         }]);
 
         let info = mock_info("owner1", &[]);     
-        let msg = ExecuteMsg::GetReward { token_ids:vec!["reveal1".to_string(),"reveal2".to_string()] };
+        let msg = ExecuteMsg::GetReward { token_ids:vec!["reveal1".to_string(),"reveal2".to_string()], instant:None };
         let res = execute(deps.as_mut(),mock_env(),info,msg).unwrap();
         assert_eq!(0,res.messages.len());
         // assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
@@ -816,25 +1378,26 @@ mod tests {
         
         let info = mock_info("owner1", &[]);     
         let msg = ExecuteMsg::WithdrawNft { token_id:"reveal1".to_string() };
-        let res = execute(deps.as_mut(),mock_env(),info,msg).unwrap();
+        let res = execute(deps.as_mut(),later(1200),info,msg).unwrap();
         
         
-        let my_ids = query_my_ids(deps.as_ref(), "owner1".to_string()).unwrap();
+        let my_ids = query_my_ids(deps.as_ref(), "owner1".to_string()).unwrap().token_ids;
         assert_eq!(my_ids,["reveal2"]);
 
         assert_eq!(1,res.messages.len());
         assert_eq!(res.messages[0].msg,CosmosMsg::Wasm(WasmMsg::Execute {
              contract_addr: "nft_address1".to_string(), 
-             msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+             msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
                   recipient: "owner1".to_string(), 
                   token_id: "reveal1".to_string() }).unwrap() , 
              funds: vec![] }));
 
-        let tokens = query_get_members(deps.as_ref()).unwrap();
+        let tokens = query_get_members(deps.as_ref()).unwrap().tokens;
         assert_eq!(tokens,vec!["reveal2"]);
 
         let id_info = query_get_token(deps.as_ref(),"reveal2".to_string()).unwrap();
-        assert_eq!(id_info,TokenInfo{
+        assert_eq!(id_info.reward_eligible_at,mock_env().block.time.seconds());
+        assert_eq!(id_info.info,TokenInfo{
             owner:"owner1".to_string(),
             token_id:"reveal2".to_string(),
             stake_time:mock_env().block.time.seconds(),
@@ -844,4 +1407,607 @@ mod tests {
         })
     
     }
+
+    pub(crate) fn instantiate_msg() -> InstantiateMsg {
+        InstantiateMsg {
+            denom: "ujuno".to_string(),
+            staking_period: 1000,
+            reward_wallet: "reward_wallet".to_string(),
+            distribute_period: 100,
+            owner: None,
+            nft_address: "nft_address1".to_string(),
+            token_address: None,
+            can_stake: true,
+            min_stake_age: None,
+            nft_weight: None,
+            transfer_unstaking: None,
+            points_rate: None,
+            reward_expiry: None,
+            receipts: None
+        }
+    }
+
+    #[test]
+    fn total_staked_counts_staked_tokens() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        for token_id in ["1", "2"] {
+            let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: "owner1".to_string(),
+                token_id: token_id.to_string(),
+                msg: to_json_binary(&"").unwrap()
+            });
+            execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+        }
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().total_staked, Uint128::new(2));
+
+        // an unstaking token no longer counts, and withdrawing it changes nothing
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().total_staked, Uint128::new(1));
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(1000);
+
+        // it still shares distributions during its cooldown
+        let mut distribute_env = mock_env();
+        distribute_env.block.time = distribute_env.block.time.plus_seconds(100);
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]);
+        execute(deps.as_mut(), distribute_env, info, ExecuteMsg::DistributeReward {}).unwrap();
+        let rewards: Vec<_> = query_token_info(deps.as_ref()).unwrap().tokens.iter().map(|token| token.reward_juno).collect();
+        assert_eq!(rewards, [Uint128::new(100), Uint128::new(100)]);
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), env, mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().total_staked, Uint128::new(1));
+    }
+
+    #[test]
+    fn unstaking_twice_is_rejected() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "owner1".to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&"").unwrap()
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg.clone()).unwrap();
+        // a second unstake would restart the cooldown and count the token out twice
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(500);
+        let err = execute(deps.as_mut(), env, mock_info("owner1", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::StatusError {}));
+        let token = query_get_token(deps.as_ref(), "1".to_string()).unwrap();
+        assert_eq!(token.info.unstake_time, mock_env().block.time.seconds());
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().total_staked, Uint128::zero());
+    }
+
+    fn stake(deps: DepsMut, env: Env, owner: &str, token_id: &str) {
+        let info = mock_info("nft_address1", &[]);
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"abc".to_string()).unwrap()
+        });
+        execute(deps, env, info, msg).unwrap();
+    }
+
+    #[test]
+    fn pending_rewards_and_pool_stats() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal1");
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal2");
+        stake(deps.as_mut(), mock_env(), "owner2", "reveal3");
+        stake(deps.as_mut(), mock_env(), "owner2", "reveal4");
+
+        let stats = query_pool_stats(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(stats.eligible_count, 4);
+        assert_eq!(stats.annual_reward_per_nft, Uint128::zero());
+
+        // one token left the pool long enough ago to stop earning
+        let msg = ExecuteMsg::UnstakeNft { token_id: "reveal4".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner2", &[]), msg).unwrap();
+
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(300) }]);
+        execute(deps.as_mut(), later(1000), info, ExecuteMsg::DistributeReward {}).unwrap();
+
+        let pending = query_pending_rewards(deps.as_ref(), "owner1".to_string()).unwrap();
+        assert_eq!(pending, PendingRewardsResponse {
+            address: "owner1".to_string(),
+            token_count: 2,
+            rewards: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]
+        });
+        let pending = query_pending_rewards(deps.as_ref(), "owner2".to_string()).unwrap();
+        assert_eq!(pending.rewards[0].amount, Uint128::new(100));
+        let pending = query_pending_rewards(deps.as_ref(), "nobody".to_string()).unwrap();
+        assert_eq!(pending.token_count, 0);
+        assert_eq!(pending.rewards[0].amount, Uint128::zero());
+
+        // a single distribution is extrapolated over distribute_period
+        let stats = query_pool_stats(deps.as_ref(), later(1000)).unwrap();
+        assert_eq!(stats.eligible_count, 3);
+        assert_eq!(stats.total_staked, Uint128::new(3));
+        assert_eq!(stats.last_distribute, later(1000).block.time.seconds());
+        assert_eq!(stats.last_distribute_amount, Uint128::new(300));
+        assert_eq!(stats.annual_reward_per_nft, Uint128::new(100 * SECONDS_PER_YEAR as u128 / 100));
+
+        // afterwards the observed interval between distributions is used
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(600) }]);
+        execute(deps.as_mut(), later(3000), info, ExecuteMsg::DistributeReward {}).unwrap();
+        let stats = query_pool_stats(deps.as_ref(), later(3000)).unwrap();
+        assert_eq!(stats.annual_reward_per_nft, Uint128::new(200 * SECONDS_PER_YEAR as u128 / 2000));
+    }
+
+    #[test]
+    fn distribution_history_records_epochs() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal1");
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal2");
+        stake(deps.as_mut(), mock_env(), "owner2", "reveal3");
+
+        for epoch in 1..=3u64 {
+            let funds = [Coin { denom: "ujuno".to_string(), amount: Uint128::new(100) }];
+            let info = mock_info("reward_wallet", &funds);
+            execute(deps.as_mut(), later(100 * epoch), info, ExecuteMsg::DistributeReward {}).unwrap();
+        }
+
+        let history = query_distribution_history(deps.as_ref(), None, None).unwrap();
+        assert_eq!(history.distributions.len(), 3);
+        assert_eq!(history.distributions[0], DistributionEpoch {
+            id: 1,
+            time: later(100).block.time.seconds(),
+            amount: Uint128::new(100),
+            denom: "ujuno".to_string(),
+            eligible_count: 3,
+            pool_rewards: Uint128::zero(),
+            carried_dust: Uint128::zero(),
+            per_token: Uint128::new(33),
+            dust: Uint128::new(1),
+        });
+        // the remainder is carried into the next epochs until it divides evenly
+        assert_eq!(history.distributions[1].carried_dust, Uint128::new(1));
+        assert_eq!(history.distributions[1].dust, Uint128::new(2));
+        assert_eq!(history.distributions[2].carried_dust, Uint128::new(2));
+        assert_eq!(history.distributions[2].per_token, Uint128::new(34));
+        assert_eq!(history.distributions[2].dust, Uint128::zero());
+
+        let dust = query_carried_dust(deps.as_ref()).unwrap();
+        assert_eq!(dust.amount, Coin { denom: "ujuno".to_string(), amount: Uint128::zero() });
+        let rewards = query_pending_rewards(deps.as_ref(), "owner1".to_string()).unwrap();
+        assert_eq!(rewards.rewards[0].amount, Uint128::new(200));
+
+        let page = query_distribution_history(deps.as_ref(), Some(1), Some(1)).unwrap();
+        assert_eq!(page.distributions.len(), 1);
+        assert_eq!(page.distributions[0].id, 2);
+        let page = query_distribution_history(deps.as_ref(), Some(3), None).unwrap();
+        assert!(page.distributions.is_empty());
+    }
+
+    #[test]
+    fn early_withdraw_penalty() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let policy = PenaltyPolicy {
+            min_stake_duration: 5000,
+            percent: 40,
+            destination: PenaltyDestination::Treasury { address: "treasury".to_string() }
+        };
+        let msg = ExecuteMsg::SetPenaltyPolicy { policy: Some(PenaltyPolicy { percent: 101, ..policy.clone() }) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPenaltyPercent { percent: 101 }));
+        let msg = ExecuteMsg::SetPenaltyPolicy { policy: Some(policy.clone()) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal1");
+        stake(deps.as_mut(), mock_env(), "owner2", "reveal2");
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]);
+        execute(deps.as_mut(), later(100), info, ExecuteMsg::DistributeReward {}).unwrap();
+
+        let msg = ExecuteMsg::UnstakeNft { token_id: "reveal1".to_string() };
+        execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+
+        let preview = query_penalty_preview(deps.as_ref(), later(1100), "reveal1".to_string()).unwrap();
+        assert_eq!(preview, PenaltyPreviewResponse {
+            token_id: "reveal1".to_string(),
+            penalty: Uint128::new(40),
+            payout: Uint128::new(60),
+            penalty_free_at: mock_env().block.time.seconds() + 5000,
+        });
+        let preview = query_penalty_preview(deps.as_ref(), later(5000), "reveal1".to_string()).unwrap();
+        assert_eq!(preview.penalty, Uint128::zero());
+
+        let msg = ExecuteMsg::WithdrawNft { token_id: "reveal1".to_string() };
+        let res = execute(deps.as_mut(), later(1100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner1".to_string(),
+            amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(60) }]
+        }));
+        assert_eq!(res.messages[2].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(40) }]
+        }));
+
+        // redistributed penalties are shared out by the next distribution
+        let msg = ExecuteMsg::SetPenaltyPolicy {
+            policy: Some(PenaltyPolicy { destination: PenaltyDestination::Redistribute {}, ..policy })
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        stake(deps.as_mut(), later(1100), "owner3", "reveal3");
+        let msg = ExecuteMsg::UnstakeNft { token_id: "reveal2".to_string() };
+        execute(deps.as_mut(), later(100), mock_info("owner2", &[]), msg).unwrap();
+        let msg = ExecuteMsg::WithdrawNft { token_id: "reveal2".to_string() };
+        let res = execute(deps.as_mut(), later(1100), mock_info("owner2", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(query_carried_dust(deps.as_ref()).unwrap().amount.amount, Uint128::new(40));
+
+        let info = mock_info("reward_wallet", &[]);
+        execute(deps.as_mut(), later(1200), info, ExecuteMsg::DistributeReward {}).unwrap();
+        let pending = query_pending_rewards(deps.as_ref(), "owner3".to_string()).unwrap();
+        assert_eq!(pending.rewards[0].amount, Uint128::new(40));
+    }
+
+    #[test]
+    fn claiming_early_pays_the_penalty() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let policy = PenaltyPolicy {
+            min_stake_duration: 5000,
+            percent: 40,
+            destination: PenaltyDestination::Treasury { address: "treasury".to_string() }
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::SetPenaltyPolicy { policy: Some(policy) })
+            .unwrap();
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal1");
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal2");
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]);
+        execute(deps.as_mut(), later(100), info, ExecuteMsg::DistributeReward {}).unwrap();
+
+        // claiming inside the window forfeits the same share a withdrawal would
+        let msg = ExecuteMsg::GetReward { token_ids: vec!["reveal1".to_string()], instant: None };
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.iter().map(|sub| sub.msg.clone()).collect::<Vec<_>>(), [
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "owner1".to_string(),
+                amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(60) }]
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(40) }]
+            }),
+        ]);
+
+        // and there is nothing left to forfeit, so the withdrawal after it pays nothing more
+        let msg = ExecuteMsg::UnstakeNft { token_id: "reveal1".to_string() };
+        execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+        let msg = ExecuteMsg::WithdrawNft { token_id: "reveal1".to_string() };
+        let res = execute(deps.as_mut(), later(1100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(res.attributes.contains(&attr("penalty", "0")));
+
+        // handing the position over is a payout too
+        let msg = ExecuteMsg::TransferStake { token_id: "reveal2".to_string(), recipient: "owner2".to_string() };
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert!(res.attributes.contains(&attr("settled", "60")));
+        assert!(res.attributes.contains(&attr("penalty", "40")));
+    }
+
+    #[test]
+    fn warm_up_delays_reward_eligibility() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { min_stake_age: Some(500), ..UpdateConfigMsg::default() });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal1");
+        stake(deps.as_mut(), later(400), "owner2", "reveal2");
+
+        let token = query_get_token(deps.as_ref(), "reveal2".to_string()).unwrap();
+        assert_eq!(token.reward_eligible_at, later(900).block.time.seconds());
+
+        // only the older token has warmed up by now
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(100) }]);
+        execute(deps.as_mut(), later(500), info, ExecuteMsg::DistributeReward {}).unwrap();
+        assert_eq!(query_get_token(deps.as_ref(), "reveal1".to_string()).unwrap().info.reward_juno, Uint128::new(100));
+        assert_eq!(query_get_token(deps.as_ref(), "reveal2".to_string()).unwrap().info.reward_juno, Uint128::zero());
+        assert_eq!(query_pool_stats(deps.as_ref(), later(899)).unwrap().eligible_count, 1);
+
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(100) }]);
+        execute(deps.as_mut(), later(900), info, ExecuteMsg::DistributeReward {}).unwrap();
+        assert_eq!(query_get_token(deps.as_ref(), "reveal1".to_string()).unwrap().info.reward_juno, Uint128::new(150));
+        assert_eq!(query_get_token(deps.as_ref(), "reveal2".to_string()).unwrap().info.reward_juno, Uint128::new(50));
+    }
+
+    #[test]
+    fn transfer_stake() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal1");
+        stake(deps.as_mut(), mock_env(), "owner1", "reveal2");
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]);
+        execute(deps.as_mut(), later(100), info, ExecuteMsg::DistributeReward {}).unwrap();
+
+        let msg = ExecuteMsg::TransferStake { token_id: "reveal1".to_string(), recipient: "owner2".to_string() };
+        let err = execute(deps.as_mut(), later(100), mock_info("owner2", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        // handing a position to its owner would only settle its reward
+        let to_owner = ExecuteMsg::TransferStake { token_id: "reveal1".to_string(), recipient: "owner1".to_string() };
+        let err = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), to_owner).unwrap_err();
+        assert!(matches!(err, ContractError::TransferToOwner {}));
+
+        // the reward earned so far goes to the previous owner
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner1".to_string(),
+            amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(100) }]
+        }));
+        let token = query_get_token(deps.as_ref(), "reveal1".to_string()).unwrap().info;
+        assert_eq!(token.owner, "owner2");
+        assert_eq!(token.status, "Staked");
+        assert_eq!(token.stake_time, mock_env().block.time.seconds());
+        assert_eq!(token.reward_juno, Uint128::zero());
+        assert_eq!(query_my_ids(deps.as_ref(), "owner1".to_string()).unwrap().token_ids, ["reveal2"]);
+        assert_eq!(query_my_ids(deps.as_ref(), "owner2".to_string()).unwrap().token_ids, ["reveal1"]);
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().total_staked, Uint128::new(2));
+
+        // the new owner keeps earning and controls the position
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]);
+        execute(deps.as_mut(), later(200), info, ExecuteMsg::DistributeReward {}).unwrap();
+        assert_eq!(query_pending_rewards(deps.as_ref(), "owner2".to_string()).unwrap().rewards[0].amount, Uint128::new(100));
+        let msg = ExecuteMsg::UnstakeNft { token_id: "reveal1".to_string() };
+        let err = execute(deps.as_mut(), later(200), mock_info("owner1", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), later(200), mock_info("owner2", &[]), msg).unwrap();
+
+        // unstaking tokens only move when the config allows it
+        let msg = ExecuteMsg::TransferStake { token_id: "reveal1".to_string(), recipient: "owner3".to_string() };
+        let err = execute(deps.as_mut(), later(200), mock_info("owner2", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::TransferWhileUnstaking {}));
+        let allow = ExecuteMsg::UpdateConfig(UpdateConfigMsg { transfer_unstaking: Some(true), ..UpdateConfigMsg::default() });
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), allow).unwrap();
+        execute(deps.as_mut(), later(200), mock_info("owner2", &[]), msg).unwrap();
+        let token = query_get_token(deps.as_ref(), "reveal1".to_string()).unwrap().info;
+        assert_eq!(token.owner, "owner3");
+        assert_eq!(token.status, "Unstaking");
+        assert_eq!(token.unstake_time, later(200).block.time.seconds());
+
+        let msg = ExecuteMsg::TransferStake { token_id: "reveal2".to_string(), recipient: long_address() };
+        let err = execute(deps.as_mut(), later(200), mock_info("owner1", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress { .. }));
+        let msg = ExecuteMsg::TransferStake { token_id: "reveal9".to_string(), recipient: "owner3".to_string() };
+        let err = execute(deps.as_mut(), later(200), mock_info("owner1", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotStaked {}));
+    }
+
+    #[test]
+    fn update_config() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { owner: Some("admin".to_string()), ..instantiate_msg() };
+        let res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        assert_eq!(res.attributes, vec![
+            attr("action", "instantiate"),
+            attr("owner", "admin"),
+            attr("nft_address", "nft_address1"),
+        ]);
+
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { can_stake: Some(false), ..UpdateConfigMsg::default() });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let state = query_state_info(deps.as_ref()).unwrap();
+        assert!(!state.can_stake);
+        assert_eq!(state.staking_period, 1000);
+
+        // handing over ownership locks the previous owner out
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+            owner: Some("admin2".to_string()),
+            nft_weight: Some(10),
+            ..UpdateConfigMsg::default()
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        assert_eq!(res.attributes, vec![
+            attr("action", "update_config"),
+            attr("owner", "admin2"),
+            attr("nft_weight", "10"),
+        ]);
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { can_stake: Some(true), ..UpdateConfigMsg::default() });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn migrate_reads_a_baseline_config() {
+        let mut deps = mock_dependencies();
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.12.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::WrongContract { .. }));
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        deps.storage.set(b"config_state", br#"{"owner":"creator","denom":"ujuno","staking_period":1000,
+            "reward_wallet":"reward_wallet","total_staked":"2","nft_address":"nft_address1","token_address":"",
+            "can_stake":true,"last_distribute":0,"distribute_period":100}"#);
+        for (token_id, status) in [("1", "Staked"), ("2", "Unstaking")] {
+            TOKENINFO.save(&mut deps.storage, token_id, &TokenInfo {
+                owner: "owner1".to_string(),
+                token_id: token_id.to_string(),
+                status: status.to_string(),
+                unstake_time: 0,
+                stake_time: 0,
+                reward_juno: Uint128::zero(),
+            }).unwrap();
+        }
+        // vesting written before the locked total was tracked
+        let tranche = VestingTranche { amount: Uint128::new(300), start: 0, end: 10, withdrawn: Uint128::new(100) };
+        VESTING.save(&mut deps.storage, "owner1", &vec![tranche]).unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1], attr("from_version", "0.1.0"));
+        let state = query_state_info(deps.as_ref()).unwrap();
+        assert_eq!(state.token_address, None);
+        assert_eq!(state.total_staked, Uint128::new(1));
+        assert_eq!(state.nft_weight, DEFAULT_NFT_WEIGHT);
+        assert_eq!(state.min_stake_age, 0);
+        assert_eq!(get_contract_version(&deps.storage).unwrap().version, CONTRACT_VERSION);
+        let height = Some(mock_env().block.height + 1);
+        let power = voting::query_voting_power_at_height(deps.as_ref(), mock_env(), "owner1".to_string(), height);
+        assert_eq!(power.unwrap().power, Uint128::new(1));
+        let history = STAKE_HISTORY.load(&deps.storage, "owner1").unwrap();
+        assert_eq!(history[1], StakeInterval { token_id: "2".to_string(), start: 0, end: Some(0) });
+        assert_eq!(VESTING_LOCKED.load(&deps.storage).unwrap(), Uint128::new(200));
+    }
+
+    /// Longer than any address the mock api accepts.
+    fn long_address() -> String {
+        "a".repeat(256)
+    }
+
+    #[test]
+    fn instantiate_rejects_invalid_inputs() {
+        let invalid_address = |field: &str, address: &str| ContractError::InvalidAddress {
+            field: field.to_string(),
+            address: address.to_string(),
+        };
+        let invalid_period = |field: &str, value: u64, min: u64| ContractError::InvalidPeriod {
+            field: field.to_string(),
+            value,
+            min,
+            max: MAX_PERIOD,
+        };
+        let cases = vec![
+            (InstantiateMsg { owner: Some("ad".to_string()), ..instantiate_msg() }, invalid_address("owner", "ad")),
+            (InstantiateMsg { reward_wallet: "rw".to_string(), ..instantiate_msg() }, invalid_address("reward_wallet", "rw")),
+            (InstantiateMsg { nft_address: "".to_string(), ..instantiate_msg() }, invalid_address("nft_address", "")),
+            (InstantiateMsg { token_address: Some(long_address()), ..instantiate_msg() }, invalid_address("token_address", &long_address())),
+            (InstantiateMsg { denom: "".to_string(), ..instantiate_msg() }, ContractError::InvalidDenom { denom: "".to_string() }),
+            (InstantiateMsg { denom: "1juno".to_string(), ..instantiate_msg() }, ContractError::InvalidDenom { denom: "1juno".to_string() }),
+            (InstantiateMsg { denom: "ju no".to_string(), ..instantiate_msg() }, ContractError::InvalidDenom { denom: "ju no".to_string() }),
+            (InstantiateMsg { staking_period: 0, ..instantiate_msg() }, invalid_period("staking_period", 0, 1)),
+            (InstantiateMsg { staking_period: u64::MAX, ..instantiate_msg() }, invalid_period("staking_period", u64::MAX, 1)),
+            (InstantiateMsg { distribute_period: 0, ..instantiate_msg() }, invalid_period("distribute_period", 0, 1)),
+            (InstantiateMsg { distribute_period: MAX_PERIOD + 1, ..instantiate_msg() }, invalid_period("distribute_period", MAX_PERIOD + 1, 1)),
+            (InstantiateMsg { min_stake_age: Some(MAX_PERIOD + 1), ..instantiate_msg() }, invalid_period("min_stake_age", MAX_PERIOD + 1, 0)),
+        ];
+        for (msg, expected) in cases {
+            let mut deps = mock_dependencies();
+            let err = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+            assert_eq!(err.to_string(), expected.to_string());
+        }
+
+        // IBC denoms are fine
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            denom: "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2".to_string(),
+            ..instantiate_msg()
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn update_config_rejects_invalid_inputs() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let cases = vec![
+            UpdateConfigMsg { owner: Some("x".to_string()), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { reward_wallet: Some(long_address()), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { nft_address: Some("".to_string()), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { token_address: Some("T".to_string()), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { staking_period: Some(0), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { staking_period: Some(MAX_PERIOD + 1), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { distribute_period: Some(0), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { distribute_period: Some(u64::MAX), ..UpdateConfigMsg::default() },
+            UpdateConfigMsg { min_stake_age: Some(MAX_PERIOD + 1), ..UpdateConfigMsg::default() },
+        ];
+        for update in cases {
+            let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(update.clone())).unwrap_err();
+            match err {
+                ContractError::InvalidAddress { .. } | ContractError::InvalidPeriod { .. } => {}
+                err => panic!("unexpected error for {:?}: {}", update, err),
+            }
+        }
+        // nothing was applied
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().staking_period, 1000);
+
+        for min_stake_duration in [0, MAX_PERIOD + 1] {
+            let policy = PenaltyPolicy {
+                min_stake_duration,
+                percent: 10,
+                destination: PenaltyDestination::Redistribute {},
+            };
+            let msg = ExecuteMsg::SetPenaltyPolicy { policy: Some(policy) };
+            let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidPeriod { .. }));
+        }
+        let policy = PenaltyPolicy {
+            min_stake_duration: 100,
+            percent: 10,
+            destination: PenaltyDestination::Treasury { address: "tr".to_string() },
+        };
+        let msg = ExecuteMsg::SetPenaltyPolicy { policy: Some(policy) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress { .. }));
+
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Native { denom: "u$d".to_string() }, weight: 10 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDenom { .. }));
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Cw20 { address: "lp".to_string() }, weight: 10 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress { .. }));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        use cosmwasm_std::from_json;
+
+        let msg = br#"{"update_config":{"staking_period":500,"staking_periods":500}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+        let msg = br#"{"unstake_nft":{"token_id":"1","owner":"someone"}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+        let msg = br#"{"get_token":{"token_id":"1","extra":true}}"#;
+        assert!(from_json::<QueryMsg>(msg).is_err());
+        // nested inputs too
+        let msg = br#"{"set_penalty_policy":{"policy":{"min_stake_duration":10,"percent":5,
+            "destination":{"redistribute":{}},"typo":1}}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+        let msg = br#"{"set_penalty_policy":{"policy":{"min_stake_duration":10,"percent":5,
+            "destination":{"treasury":{"address":"tr","memo":""}}}}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+        let msg = br#"{"set_pool":{"asset":{"cw20":{"address":"lp","denom":"ujuno"}},"weight":1}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+
+        let mut msg = to_json_binary(&instantiate_msg()).unwrap().to_vec();
+        assert!(from_json::<InstantiateMsg>(&msg).is_ok());
+        msg.pop();
+        msg.extend_from_slice(br#","admin":"creator"}"#);
+        assert!(from_json::<InstantiateMsg>(&msg).is_err());
+    }
+
+    #[test]
+    fn clock_going_backwards_is_an_overflow() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), later(500), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), later(500), "owner", "1");
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), later(500), mock_info("owner", &[]), msg).unwrap();
+
+        // a block time before the last distribution or the unstake must not panic
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(100) }]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::DistributeReward {}).unwrap_err();
+        assert!(matches!(err, ContractError::Overflow { .. }));
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Overflow { .. }));
+    }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -35,4 +35,127 @@ pub enum ContractError {
     
     #[error("Stkaing process")]
     StatusError {},
+
+    #[error("Penalty percent must be between 0 and 100, got {percent}")]
+    InvalidPenaltyPercent { percent: u64 },
+
+    #[error("Instant claim haircut must be between 0 and 100 percent, got {percent}")]
+    InvalidHaircutPercent { percent: u64 },
+
+    #[error("Only {balance} is held and {locked} of it is vesting rewards")]
+    VestingLocked { balance: Uint128, locked: Uint128 },
+
+    #[error("Set bonus must be above 0 percent, got {percent}")]
+    InvalidSetBonus { percent: u64 },
+
+    #[error("Set pieces must be non-empty and distinct")]
+    InvalidSetPieces {},
+
+    #[error("Set {name} is already defined")]
+    SetAlreadyDefined { name: String },
+
+    #[error("Unknown set {name}")]
+    UnknownSet { name: String },
+
+    #[error("No more than {max} sets can be defined")]
+    TooManySets { max: u64 },
+
+    #[error("No staking pool for {pool}")]
+    UnknownPool { pool: String },
+
+    #[error("Amount must be above zero")]
+    ZeroAmount {},
+
+    #[error("Send exactly one coin of a pool denom")]
+    InvalidFunds {},
+
+    #[error("The reward denom can not be staked")]
+    RewardDenomPool {},
+
+    #[error("Can not migrate from contract {contract}")]
+    WrongContract { contract: String },
+
+    #[error("Invalid {field} address: {address}")]
+    InvalidAddress { field: String, address: String },
+
+    #[error("Invalid native denom: {denom}")]
+    InvalidDenom { denom: String },
+
+    #[error("{field} must be between {min} and {max} seconds, got {value}")]
+    InvalidPeriod { field: String, value: u64, min: u64, max: u64 },
+
+    #[error("Unknown reply id {id}")]
+    UnknownReply { id: u64 },
+
+    #[error("Receipt collection instantiation failed: {error}")]
+    InstantiateFailed { error: String },
+
+    #[error("Send the receipt back to withdraw this token")]
+    ReceiptRequired {},
+
+    #[error("This contract does not mint receipts")]
+    NoReceipts {},
+
+    #[error("Unstaking tokens can not be transferred")]
+    TransferWhileUnstaking {},
+
+    #[error("The recipient already owns this position")]
+    TransferToOwner {},
+
+    #[error("The position moves with its receipt, transfer the receipt instead")]
+    TransferReceipt {},
+
+    #[error("Hook is already registered")]
+    HookAlreadyRegistered {},
+
+    #[error("Hook is not registered")]
+    HookNotRegistered {},
+
+    #[error("Spender is already registered")]
+    SpenderAlreadyRegistered {},
+
+    #[error("Spender is not registered")]
+    SpenderNotRegistered {},
+
+    #[error("Not enough points: {balance} available, {amount} requested")]
+    InsufficientPoints { balance: Uint128, amount: Uint128 },
+
+    #[error("No expired rewards to sweep")]
+    NothingExpired {},
+
+    #[error("Raffle commitment must be a 32 byte sha256 hash")]
+    InvalidCommitment {},
+
+    #[error("Secret does not match the raffle commitment")]
+    WrongSecret {},
+
+    #[error("Unknown raffle {raffle_id}")]
+    UnknownRaffle { raffle_id: u64 },
+
+    #[error("Raffle {raffle_id} is no longer open")]
+    RaffleClosed { raffle_id: u64 },
+
+    #[error("Raffle draw time must be in the future")]
+    InvalidDrawTime {},
+
+    #[error("Raffle {raffle_id} is past its draw time")]
+    RaffleDue { raffle_id: u64 },
+
+    #[error("Raffle {raffle_id} must be sealed before the draw")]
+    RaffleNotSealed { raffle_id: u64 },
+
+    #[error("Not the drand signature of round {round}")]
+    InvalidBeacon { round: u64 },
+
+    #[error("Raffle {raffle_id} can only be drawn with its secret until {deadline}")]
+    RevealPending { raffle_id: u64, deadline: u64 },
+
+    #[error("Overflow: {reason}")]
+    Overflow { reason: String },
+}
+
+impl From<OverflowError> for ContractError {
+    fn from(err: OverflowError) -> Self {
+        ContractError::Overflow { reason: err.to_string() }
+    }
 }
@@ -0,0 +1,224 @@
+use cosmwasm_std::{Deps, DepsMut, Env, MessageInfo, Response, StdResult, Storage, Uint128};
+
+use crate::contract::{reward_payout, validate_address};
+use crate::error::ContractError;
+use crate::math::checked_add;
+use crate::msg::{ExpiringReward, ExpiringRewardsResponse};
+use crate::state::{PenaltyDestination, RewardCredit, CONFIG, OWNEDTOKEN, REWARD_CREDITS, TOKENINFO};
+
+/// Remembers that `token_id` was credited `amount` by distribution `epoch`.
+/// The credits of a token always add up to its `reward_juno`.
+pub(crate) fn record_credit(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    epoch: u64,
+    time: u64,
+    amount: Uint128,
+) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    REWARD_CREDITS.update(storage, token_id, |credits| -> StdResult<_> {
+        let mut credits = credits.unwrap_or_default();
+        credits.push(RewardCredit { epoch, time, amount });
+        Ok(credits)
+    })?;
+    Ok(())
+}
+
+/// Called whenever the `reward_juno` of a token is paid out or forfeited.
+pub(crate) fn clear_credits(storage: &mut dyn Storage, token_id: &str) {
+    REWARD_CREDITS.remove(storage, token_id);
+}
+
+/// `None` while rewards do not expire.
+fn expires_at(credit: &RewardCredit, reward_expiry: u64) -> StdResult<Option<u64>> {
+    if reward_expiry == 0 {
+        return Ok(None);
+    }
+    Ok(Some(checked_add(credit.time, reward_expiry)?))
+}
+
+/// Takes the expired rewards of `token_ids` back. Anyone can return them to
+/// the next distribution, only the owner can send them to a treasury.
+pub fn execute_sweep_expired(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Vec<String>,
+    destination: Option<PenaltyDestination>,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+    let destination = destination.unwrap_or(PenaltyDestination::Redistribute {});
+
+    if let PenaltyDestination::Treasury { address } = &destination {
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        validate_address(deps.api, "treasury", address)?;
+    }
+
+    let mut swept = Uint128::zero();
+    for token_id in token_ids {
+        let mut token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+            Some(token) => token,
+            None => return Err(ContractError::NotStaked {}),
+        };
+        let mut expired = Uint128::zero();
+        let mut live = vec![];
+        for credit in REWARD_CREDITS.may_load(deps.storage, &token_id)?.unwrap_or_default() {
+            match expires_at(&credit, state.reward_expiry)? {
+                Some(expires_at) if expires_at <= now => expired = expired.checked_add(credit.amount)?,
+                _ => live.push(credit),
+            }
+        }
+        if expired.is_zero() {
+            continue;
+        }
+        token.reward_juno = token.reward_juno.checked_sub(expired)?;
+        TOKENINFO.save(deps.storage, &token_id, &token)?;
+        REWARD_CREDITS.save(deps.storage, &token_id, &live)?;
+        swept = swept.checked_add(expired)?;
+    }
+    if swept.is_zero() {
+        return Err(ContractError::NothingExpired {});
+    }
+
+    let mut response = Response::new()
+        .add_attribute("action", "sweep_expired")
+        .add_attribute("swept", swept);
+    match destination {
+        PenaltyDestination::Treasury { address } => {
+            response = response.add_message(reward_payout(&state, &address, swept));
+        }
+        PenaltyDestination::Redistribute {} => {
+            CONFIG.update(deps.storage, |mut state| -> Result<_, ContractError> {
+                state.reward_dust = state.reward_dust.checked_add(swept)?;
+                Ok(state)
+            })?;
+        }
+    }
+    Ok(response)
+}
+
+/// Every unclaimed credit of the tokens of `address`, soonest to expire first.
+pub fn query_expiring_rewards(deps: Deps, env: Env, address: String) -> StdResult<ExpiringRewardsResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let mut rewards = vec![];
+    for token_id in OWNEDTOKEN.may_load(deps.storage, &address)?.unwrap_or_default() {
+        for credit in REWARD_CREDITS.may_load(deps.storage, &token_id)?.unwrap_or_default() {
+            rewards.push(ExpiringReward {
+                token_id: token_id.clone(),
+                epoch: credit.epoch,
+                amount: credit.amount,
+                expires_at: expires_at(&credit, state.reward_expiry)?,
+            });
+        }
+    }
+    rewards.sort_by_key(|reward| (reward.expires_at, reward.epoch));
+
+    let expired = rewards
+        .iter()
+        .filter(|reward| matches!(reward.expires_at, Some(expires_at) if expires_at <= now))
+        .try_fold(Uint128::zero(), |total, reward| total.checked_add(reward.amount))?;
+
+    Ok(ExpiringRewardsResponse { address, expired, rewards })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate, query_state_info};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, to_json_binary, BankMsg, CosmosMsg, DepsMut};
+    use cw721::Cw721ReceiveMsg;
+
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn stake(deps: DepsMut, owner: &str, token_id: &str) {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+    }
+
+    fn distribute(deps: DepsMut, seconds: u64, amount: u128) {
+        let info = mock_info("reward_wallet", &coins(amount, "ujuno"));
+        execute(deps, later(seconds), info, ExecuteMsg::DistributeReward {}).unwrap();
+    }
+
+    fn sweep(token_ids: &[&str], destination: Option<PenaltyDestination>) -> ExecuteMsg {
+        ExecuteMsg::SweepExpiredRewards {
+            token_ids: token_ids.iter().map(|id| id.to_string()).collect(),
+            destination,
+        }
+    }
+
+    #[test]
+    fn expired_credits_are_swept_per_epoch() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { reward_expiry: Some(1000), ..instantiate_msg() };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        stake(deps.as_mut(), "owner1", "1");
+        stake(deps.as_mut(), "owner2", "2");
+        distribute(deps.as_mut(), 100, 200);
+        distribute(deps.as_mut(), 600, 400);
+
+        let res = query_expiring_rewards(deps.as_ref(), later(1100), "owner1".to_string()).unwrap();
+        let expiring: Vec<_> = res.rewards.iter().map(|reward| (reward.epoch, reward.amount.u128(), reward.expires_at)).collect();
+        let start = mock_env().block.time.seconds();
+        assert_eq!(expiring, [(1, 100, Some(start + 1100)), (2, 200, Some(start + 1600))]);
+        assert_eq!(res.expired, Uint128::new(100));
+
+        // owner2 claimed in time, only the first epoch of owner1 expired
+        let claim = ExecuteMsg::GetReward { token_ids: vec!["2".to_string()], instant: None };
+        execute(deps.as_mut(), later(700), mock_info("owner2", &[]), claim).unwrap();
+        let err = execute(deps.as_mut(), later(1099), mock_info("anyone", &[]), sweep(&["1", "2"], None)).unwrap_err();
+        assert!(matches!(err, ContractError::NothingExpired {}));
+        let res = execute(deps.as_mut(), later(1100), mock_info("anyone", &[]), sweep(&["1", "2"], None)).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().reward_dust, Uint128::new(100));
+        let token = TOKENINFO.load(&deps.storage, "1").unwrap();
+        assert_eq!(token.reward_juno, Uint128::new(200));
+        assert_eq!(query_expiring_rewards(deps.as_ref(), later(1100), "owner1".to_string()).unwrap().rewards.len(), 1);
+
+        // only the owner sends them to a treasury
+        let treasury = Some(PenaltyDestination::Treasury { address: "treasury".to_string() });
+        let err = execute(deps.as_mut(), later(1600), mock_info("anyone", &[]), sweep(&["1"], treasury.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), later(1600), mock_info("creator", &[]), sweep(&["1"], treasury)).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(200, "ujuno"),
+        }));
+        assert_eq!(TOKENINFO.load(&deps.storage, "1").unwrap().reward_juno, Uint128::zero());
+
+        let err = execute(deps.as_mut(), later(1600), mock_info("anyone", &[]), sweep(&["9"], None)).unwrap_err();
+        assert!(matches!(err, ContractError::NotStaked {}));
+    }
+
+    #[test]
+    fn rewards_never_expire_by_default() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), "owner1", "1");
+        distribute(deps.as_mut(), 100, 200);
+
+        let res = query_expiring_rewards(deps.as_ref(), later(1_000_000), "owner1".to_string()).unwrap();
+        assert_eq!(res.rewards[0].expires_at, None);
+        assert_eq!(res.expired, Uint128::zero());
+        let err = execute(deps.as_mut(), later(1_000_000), mock_info("anyone", &[]), sweep(&["1"], None)).unwrap_err();
+        assert!(matches!(err, ContractError::NothingExpired {}));
+    }
+}
@@ -0,0 +1,163 @@
+use cosmwasm_std::{to_json_binary, CosmosMsg, Deps, DepsMut, MessageInfo, Response, StdResult, Storage, WasmMsg};
+
+use crate::contract::validate_address;
+use crate::error::ContractError;
+use crate::msg::{HooksResponse, StakeChangedExecuteMsg, StakeChangedHookMsg};
+use crate::state::{CONFIG, HOOKS};
+
+pub fn execute_add_hook(deps: DepsMut, info: MessageInfo, addr: String) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let addr = validate_address(deps.api, "hook", &addr)?;
+
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if hooks.contains(&addr) {
+        return Err(ContractError::HookAlreadyRegistered {});
+    }
+    hooks.push(addr.clone());
+    HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_hook")
+        .add_attribute("hook", addr))
+}
+
+pub fn execute_remove_hook(deps: DepsMut, info: MessageInfo, addr: String) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    match hooks.iter().position(|hook| *hook == addr) {
+        Some(index) => hooks.remove(index),
+        None => return Err(ContractError::HookNotRegistered {}),
+    };
+    HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_hook")
+        .add_attribute("hook", addr))
+}
+
+/// One message per registered hook. They are sent without a reply, so a hook
+/// that fails reverts the change it was told about.
+pub(crate) fn stake_changed_hooks(storage: &dyn Storage, msg: StakeChangedHookMsg) -> StdResult<Vec<CosmosMsg>> {
+    let hooks = HOOKS.may_load(storage)?.unwrap_or_default();
+    if hooks.is_empty() {
+        return Ok(vec![]);
+    }
+    let msg = to_json_binary(&StakeChangedExecuteMsg::StakeChangedHook(msg))?;
+    Ok(hooks
+        .into_iter()
+        .map(|contract_addr| {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg: msg.clone(),
+                funds: vec![],
+            })
+        })
+        .collect())
+}
+
+pub fn query_hooks(deps: Deps) -> StdResult<HooksResponse> {
+    Ok(HooksResponse {
+        hooks: HOOKS.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate};
+    use crate::msg::ExecuteMsg;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cw721::Cw721ReceiveMsg;
+
+    fn hook(contract_addr: &str, owner: &str, old_status: Option<&str>, new_status: Option<&str>) -> CosmosMsg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_json_binary(&StakeChangedExecuteMsg::StakeChangedHook(StakeChangedHookMsg {
+                owner: owner.to_string(),
+                token_id: "1".to_string(),
+                old_status: old_status.map(str::to_string),
+                new_status: new_status.map(str::to_string),
+            }))
+            .unwrap(),
+            funds: vec![],
+        })
+    }
+
+    #[test]
+    fn registered_hooks_follow_every_status_change() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let add = |addr: &str| ExecuteMsg::AddHook { addr: addr.to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), add("game")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        for addr in ["game", "dao", "loyalty"] {
+            execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), add(addr)).unwrap();
+        }
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), add("dao")).unwrap_err();
+        assert!(matches!(err, ContractError::HookAlreadyRegistered {}));
+
+        let remove = |addr: &str| ExecuteMsg::RemoveHook { addr: addr.to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), remove("loyalty")).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), remove("loyalty")).unwrap_err();
+        assert!(matches!(err, ContractError::HookNotRegistered {}));
+        assert_eq!(query_hooks(deps.as_ref()).unwrap().hooks, ["game", "dao"]);
+
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "owner1".to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+        let sent: Vec<_> = res.messages.into_iter().map(|msg| msg.msg).collect();
+        assert_eq!(sent, [
+            hook("game", "owner1", None, Some("Staked")),
+            hook("dao", "owner1", None, Some("Staked")),
+        ]);
+
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages[0].msg, hook("game", "owner1", Some("Staked"), Some("Unstaking")));
+        assert_eq!(res.messages.len(), 2);
+
+        // after the nft transfer
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(1000);
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        let res = execute(deps.as_mut(), env, mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(res.messages[2].msg, hook("dao", "owner1", Some("Unstaking"), None));
+    }
+
+    #[test]
+    fn transfers_tell_both_owners() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::AddHook { addr: "dao".to_string() })
+            .unwrap();
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "owner1".to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::TransferStake { token_id: "1".to_string(), recipient: "owner2".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap();
+        let sent: Vec<_> = res.messages.into_iter().map(|msg| msg.msg).collect();
+        assert_eq!(sent, [
+            hook("dao", "owner1", Some("Staked"), None),
+            hook("dao", "owner2", None, Some("Staked")),
+        ]);
+    }
+}
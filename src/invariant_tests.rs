@@ -0,0 +1,289 @@
+//! Randomized sequences of staking actions checked against a small model of
+//! the NFT pool. After every step the reward funds must be conserved and the
+//! token indexes must agree with each other.
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    to_json_binary, BankMsg, Coin, CosmosMsg, Env, Order, OwnedDeps, Response, Storage, Uint128,
+};
+use cw721::Cw721ReceiveMsg;
+use proptest::prelude::*;
+
+use crate::contract::tests::instantiate_msg;
+use crate::contract::{execute, instantiate};
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg};
+use crate::state::{
+    CollectionSet, SetPieces, COMPLETED_SETS, CONFIG, OWNEDTOKEN, REWARD_CREDITS, STAKED_POWER, TOKENINFO, TOTAL_POWER,
+};
+
+const USERS: usize = 4;
+const TOKENS: usize = 10;
+const DENOM: &str = "ujuno";
+
+#[derive(Clone, Debug)]
+enum Action {
+    Stake { user: usize, token: usize },
+    Unstake { user: usize, token: usize },
+    Withdraw { user: usize, token: usize },
+    Claim { user: usize, tokens: Vec<usize> },
+    Transfer { user: usize, token: usize, recipient: usize },
+    Distribute { amount: u128 },
+    Sweep { tokens: Vec<usize> },
+    Advance { seconds: u64 },
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        (0..USERS, 0..TOKENS).prop_map(|(user, token)| Action::Stake { user, token }),
+        (0..USERS, 0..TOKENS).prop_map(|(user, token)| Action::Unstake { user, token }),
+        (0..USERS, 0..TOKENS).prop_map(|(user, token)| Action::Withdraw { user, token }),
+        (0..USERS, prop::collection::vec(0..TOKENS, 0..4))
+            .prop_map(|(user, tokens)| Action::Claim { user, tokens }),
+        (0..USERS, 0..TOKENS, 0..USERS)
+            .prop_map(|(user, token, recipient)| Action::Transfer { user, token, recipient }),
+        (0..1_000_000u128).prop_map(|amount| Action::Distribute { amount }),
+        prop::collection::vec(0..TOKENS, 1..4).prop_map(|tokens| Action::Sweep { tokens }),
+        (0..600u64).prop_map(|seconds| Action::Advance { seconds }),
+    ]
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Status {
+    Staked,
+    Unstaking { since: u64 },
+}
+
+/// What the contract should know about each token, and the reward funds that
+/// went in and out of it.
+#[derive(Default)]
+struct Model {
+    tokens: BTreeMap<usize, (usize, Status)>,
+    funded: Uint128,
+    paid_out: Uint128,
+}
+
+struct Harness {
+    deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    env: Env,
+    model: Model,
+    staking_period: u64,
+}
+
+fn user(index: usize) -> String {
+    format!("user{}", index)
+}
+
+fn token(index: usize) -> String {
+    format!("token{}", index)
+}
+
+fn snapshot(storage: &dyn Storage) -> Vec<(Vec<u8>, Vec<u8>)> {
+    storage.range(None, None, Order::Ascending).collect()
+}
+
+impl Harness {
+    fn new() -> Self {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { reward_expiry: Some(1000), ..instantiate_msg() };
+        let staking_period = msg.staking_period;
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        // staking the first two tokens together earns a bonus
+        let set = CollectionSet {
+            name: "pair".to_string(),
+            bonus_percent: 50,
+            pieces: SetPieces::Tokens { token_ids: vec![token(0), token(1)] },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::AddCollectionSet { set }).unwrap();
+        Harness { deps, env: mock_env(), model: Model::default(), staking_period }
+    }
+
+    fn now(&self) -> u64 {
+        self.env.block.time.seconds()
+    }
+
+    /// Runs `msg` like a transaction: the writes of a failed call are rolled
+    /// back as the chain would, a successful one has its payouts recorded.
+    fn run(&mut self, sender: &str, msg: ExecuteMsg, funds: &[Coin]) -> Result<Response, ContractError> {
+        let before = snapshot(&self.deps.storage);
+        let res = execute(self.deps.as_mut(), self.env.clone(), mock_info(sender, funds), msg);
+        match &res {
+            Ok(res) => {
+                for msg in res.messages.iter() {
+                    if let CosmosMsg::Bank(BankMsg::Send { amount, .. }) = &msg.msg {
+                        for coin in amount.iter().filter(|coin| coin.denom == DENOM) {
+                            self.model.paid_out += coin.amount;
+                        }
+                    }
+                }
+            }
+            Err(_) => {
+                self.deps.storage = MockStorage::new();
+                for (key, value) in before {
+                    self.deps.storage.set(&key, &value);
+                }
+            }
+        }
+        res
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Stake { user: index, token: id } => {
+                let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                    sender: user(index),
+                    token_id: token(id),
+                    msg: to_json_binary(&"").unwrap(),
+                });
+                let res = self.run("nft_address1", msg, &[]);
+                assert_eq!(res.is_ok(), !self.model.tokens.contains_key(&id), "{:?}", res);
+                if res.is_ok() {
+                    self.model.tokens.insert(id, (index, Status::Staked));
+                }
+            }
+            Action::Unstake { user: index, token: id } => {
+                let res = self.run(&user(index), ExecuteMsg::UnstakeNft { token_id: token(id) }, &[]);
+                let expected = self.model.tokens.get(&id) == Some(&(index, Status::Staked));
+                assert_eq!(res.is_ok(), expected, "{:?}", res);
+                if res.is_ok() {
+                    let now = self.now();
+                    self.model.tokens.insert(id, (index, Status::Unstaking { since: now }));
+                }
+            }
+            Action::Withdraw { user: index, token: id } => {
+                let res = self.run(&user(index), ExecuteMsg::WithdrawNft { token_id: token(id) }, &[]);
+                let expected = match self.model.tokens.get(&id) {
+                    Some((owner, Status::Unstaking { since })) => {
+                        *owner == index && self.now() - since >= self.staking_period
+                    }
+                    _ => false,
+                };
+                assert_eq!(res.is_ok(), expected, "{:?}", res);
+                if res.is_ok() {
+                    self.model.tokens.remove(&id);
+                }
+            }
+            Action::Claim { user: index, tokens } => {
+                let token_ids = tokens.iter().map(|id| token(*id)).collect();
+                let res = self.run(&user(index), ExecuteMsg::GetReward { token_ids, instant: None }, &[]);
+                let expected = tokens
+                    .iter()
+                    .all(|id| matches!(self.model.tokens.get(id), Some((owner, _)) if *owner == index));
+                assert_eq!(res.is_ok(), expected, "{:?}", res);
+            }
+            Action::Transfer { user: index, token: id, recipient } => {
+                let msg = ExecuteMsg::TransferStake { token_id: token(id), recipient: user(recipient) };
+                let res = self.run(&user(index), msg, &[]);
+                // transfers of unstaking tokens are off by default, and to the owner never
+                let expected = self.model.tokens.get(&id) == Some(&(index, Status::Staked)) && recipient != index;
+                assert_eq!(res.is_ok(), expected, "{:?}", res);
+                if res.is_ok() {
+                    self.model.tokens.insert(id, (recipient, Status::Staked));
+                }
+            }
+            Action::Distribute { amount } => {
+                let funds = [Coin { denom: DENOM.to_string(), amount: Uint128::new(amount) }];
+                if self.run("reward_wallet", ExecuteMsg::DistributeReward {}, &funds).is_ok() {
+                    self.model.funded += Uint128::new(amount);
+                }
+            }
+            Action::Sweep { tokens } => {
+                let token_ids = tokens.iter().map(|id| token(*id)).collect();
+                let msg = ExecuteMsg::SweepExpiredRewards { token_ids, destination: None };
+                let res = self.run(&user(0), msg, &[]);
+                // whether anything had expired is left to the conservation check
+                if !tokens.iter().all(|id| self.model.tokens.contains_key(id)) {
+                    assert!(res.is_err());
+                }
+            }
+            Action::Advance { seconds } => {
+                self.env.block.time = self.env.block.time.plus_seconds(seconds);
+                self.env.block.height += 1;
+            }
+        }
+    }
+
+    fn check_invariants(&self) {
+        let storage = &self.deps.storage;
+        let state = CONFIG.load(storage).unwrap();
+        let tokens: Vec<_> = TOKENINFO
+            .range(storage, None, None, Order::Ascending)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        // every unit of reward is either paid out, owed to a token or carried as dust
+        let owed = tokens
+            .iter()
+            .fold(Uint128::zero(), |total, (_, info)| total + info.reward_juno);
+        assert_eq!(self.model.funded, self.model.paid_out + owed + state.reward_dust);
+
+        // the unclaimed reward of every token is accounted for by its credits
+        for (id, info) in tokens.iter() {
+            let credits = REWARD_CREDITS.may_load(storage, id).unwrap().unwrap_or_default();
+            let credited = credits.iter().fold(Uint128::zero(), |total, credit| total + credit.amount);
+            assert_eq!(credited, info.reward_juno, "credits of {}", id);
+        }
+        for id in REWARD_CREDITS.keys(storage, None, None, Order::Ascending) {
+            let id = id.unwrap();
+            assert!(TOKENINFO.has(storage, &id), "credits left behind by {}", id);
+        }
+
+        let staked = tokens.iter().filter(|(_, info)| info.status == "Staked").count();
+        assert_eq!(state.total_staked, Uint128::from(staked as u128));
+
+        // OWNEDTOKEN is an exact index of TOKENINFO by owner
+        let mut indexed = 0;
+        for item in OWNEDTOKEN.range(storage, None, None, Order::Ascending) {
+            let (owner, ids) = item.unwrap();
+            let mut unique = ids.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), ids.len(), "duplicate ids for {}", owner);
+            for id in ids.iter() {
+                assert_eq!(TOKENINFO.load(storage, id).unwrap().owner, owner);
+            }
+            indexed += ids.len();
+        }
+        assert_eq!(indexed, tokens.len());
+
+        // voting power counts the staked tokens of each owner
+        assert_eq!(TOTAL_POWER.may_load(storage).unwrap().unwrap_or_default(), state.total_staked);
+        for index in 0..USERS {
+            let staked = self.model.tokens.values().filter(|token| **token == (index, Status::Staked)).count();
+            let power = STAKED_POWER.may_load(storage, &user(index)).unwrap().unwrap_or_default();
+            assert_eq!(power, Uint128::from(staked as u128), "power of {}", user(index));
+
+            let staked = |id| self.model.tokens.get(&id) == Some(&(index, Status::Staked));
+            let completed = COMPLETED_SETS.has(storage, ("pair", &user(index)));
+            assert_eq!(completed, staked(0) && staked(1), "pair of {}", user(index));
+        }
+
+        // and both agree with the model
+        assert_eq!(tokens.len(), self.model.tokens.len());
+        for (id, (owner, status)) in self.model.tokens.iter() {
+            let info = TOKENINFO.load(storage, &token(*id)).unwrap();
+            assert_eq!(info.owner, user(*owner));
+            match status {
+                Status::Staked => assert_eq!(info.status, "Staked"),
+                Status::Unstaking { since } => {
+                    assert_eq!(info.status, "Unstaking");
+                    assert_eq!(info.unstake_time, *since);
+                }
+            }
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn rewards_are_conserved(actions in prop::collection::vec(action(), 1..80)) {
+        let mut harness = Harness::new();
+        for action in actions {
+            harness.apply(action);
+            harness.check_invariants();
+        }
+    }
+}
@@ -1,4 +1,21 @@
+#![allow(non_snake_case)]
+
+mod beacon;
 pub mod contract;
 mod error;
+mod expiry;
+mod hooks;
+mod math;
 pub mod msg;
+mod points;
+mod pools;
+mod raffle;
+mod receipts;
+mod sets;
+pub mod snapshot;
 pub mod state;
+mod vesting;
+mod voting;
+
+#[cfg(test)]
+mod invariant_tests;
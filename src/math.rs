@@ -0,0 +1,82 @@
+//! Overflow-checked helpers for the reward and time math. Every error is an
+//! `OverflowError`, which turns into `ContractError::Overflow` in execute
+//! handlers and into a `StdError` in queries.
+use std::convert::TryInto;
+
+use cosmwasm_std::{OverflowError, OverflowOperation, Uint128, Uint256};
+
+pub fn checked_add(a: u64, b: u64) -> Result<u64, OverflowError> {
+    a.checked_add(b)
+        .ok_or_else(|| OverflowError::new(OverflowOperation::Add, a, b))
+}
+
+pub fn checked_sub(a: u64, b: u64) -> Result<u64, OverflowError> {
+    a.checked_sub(b)
+        .ok_or_else(|| OverflowError::new(OverflowOperation::Sub, a, b))
+}
+
+/// `value * numerator / denominator` without the panics of
+/// `Uint128::multiply_ratio`. A zero denominator is reported as an overflow too.
+pub fn checked_multiply_ratio(
+    value: Uint128,
+    numerator: impl Into<u128>,
+    denominator: impl Into<u128>,
+) -> Result<Uint128, OverflowError> {
+    let numerator: u128 = numerator.into();
+    let denominator: u128 = denominator.into();
+    let overflow = || OverflowError::new(OverflowOperation::Mul, value, numerator);
+    if denominator == 0 {
+        return Err(overflow());
+    }
+    (value.full_mul(numerator) / Uint256::from(denominator))
+        .try_into()
+        .map_err(|_| overflow())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn add_and_sub_match_u64(a in any::<u64>(), b in any::<u64>()) {
+            prop_assert_eq!(checked_add(a, b).ok(), a.checked_add(b));
+            prop_assert_eq!(checked_sub(a, b).ok(), a.checked_sub(b));
+        }
+
+        #[test]
+        fn multiply_ratio_never_panics(value in any::<u128>(), numerator in any::<u128>(), denominator in any::<u128>()) {
+            let result = checked_multiply_ratio(Uint128::new(value), numerator, denominator);
+            if denominator == 0 {
+                prop_assert!(result.is_err());
+            } else if numerator <= denominator {
+                // a share of a total always fits and never exceeds it
+                let share = result.unwrap();
+                prop_assert!(share <= Uint128::new(value));
+                prop_assert_eq!(share, Uint128::new(value).multiply_ratio(numerator, denominator));
+            }
+        }
+
+        #[test]
+        fn shares_never_exceed_the_total(total in any::<u128>(), weights in prop::collection::vec(0..1_000_000u64, 1..8)) {
+            let total_weight: u64 = weights.iter().sum();
+            prop_assume!(total_weight > 0);
+            let credited = weights.iter().try_fold(Uint128::zero(), |sum, weight| {
+                sum.checked_add(checked_multiply_ratio(Uint128::new(total), *weight, total_weight)?)
+            });
+            prop_assert!(credited.unwrap() <= Uint128::new(total));
+        }
+    }
+
+    #[test]
+    fn edge_cases() {
+        assert!(checked_add(u64::MAX, 1).is_err());
+        assert_eq!(checked_add(u64::MAX, 0).unwrap(), u64::MAX);
+        assert!(checked_sub(0, 1).is_err());
+        assert_eq!(checked_sub(5, 5).unwrap(), 0);
+        assert!(checked_multiply_ratio(Uint128::MAX, 2u128, 1u128).is_err());
+        assert_eq!(checked_multiply_ratio(Uint128::MAX, 7u128, 7u128).unwrap(), Uint128::MAX);
+        assert!(checked_multiply_ratio(Uint128::new(1), 1u128, 0u128).is_err());
+    }
+}
@@ -1,39 +1,180 @@
-use cosmwasm_std::{ Uint128};
+use cosmwasm_std::{Coin, HexBinary, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use cw20::Cw20ReceiveMsg;
 use cw721::Cw721ReceiveMsg;
 
+use crate::state::{
+    CollectionSet, DistributionEpoch, FungiblePool, LockedAmount, PenaltyDestination, PenaltyPolicy, PointSpend,
+    PoolAsset, Raffle, TokenInfo,
+    VestingPolicy, VestingTranche
+};
+
 
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct InstantiateMsg {
     pub denom:String,
     pub staking_period : u64,
     pub reward_wallet : String,
-    pub distribute_period: u64
+    pub distribute_period: u64,
+    /// Defaults to the sender of the instantiate message.
+    pub owner: Option<String>,
+    /// The cw721 collection that can be staked.
+    pub nft_address: String,
+    /// Optional CW20 reward token.
+    pub token_address: Option<String>,
+    pub can_stake: bool,
+    pub min_stake_age: Option<u64>,
+    pub nft_weight: Option<u64>,
+    /// Defaults to only transferring staked tokens.
+    pub transfer_unstaking: Option<bool>,
+    /// Loyalty points per staked token per second, none by default.
+    pub points_rate: Option<u64>,
+    /// Seconds before unclaimed rewards can be swept, never by default.
+    pub reward_expiry: Option<u64>,
+    /// Mint a tradeable receipt for every staked NFT.
+    pub receipts: Option<ReceiptConfig>
 }
 
+/// The receipt collection is a staking-receipts instance administered by the
+/// contract owner, with the staking contract as its minter.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
+#[serde(deny_unknown_fields)]
+pub struct ReceiptConfig {
+    /// Code id of staking-receipts. A plain cw721-base works too, but its
+    /// transfers only move positions on `SyncReceipts`, a claim or an unstake.
+    pub code_id: u64,
+    pub name: String,
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MigrateMsg {}
+
+/// Every field that is set is changed, the others are left as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateConfigMsg {
+    pub owner: Option<String>,
+    pub reward_wallet: Option<String>,
+    pub nft_address: Option<String>,
+    pub token_address: Option<String>,
+    pub staking_period: Option<u64>,
+    pub distribute_period: Option<u64>,
+    pub can_stake: Option<bool>,
+    pub min_stake_age: Option<u64>,
+    pub nft_weight: Option<u64>,
+    pub transfer_unstaking: Option<bool>,
+    pub points_rate: Option<u64>,
+    pub reward_expiry: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ExecuteMsg {
+    /// Stakes an NFT of the staked collection, or withdraws the position of a
+    /// receipt sent back from the receipt collection. An NFT sent with a
+    /// `ReceiveNftMsg` is a raffle prize instead.
     ReceiveNft(Cw721ReceiveMsg),
     UnstakeNft{token_id:String},
     WithdrawNft{token_id:String},
-    GetReward{token_ids:Vec<String>},  
+    /// With a vesting policy the claim vests, unless `instant` takes it now
+    /// less the haircut.
+    GetReward{token_ids:Vec<String>, instant:Option<bool>},
+    /// Moves the positions of `token_ids` to whoever holds their receipts now,
+    /// with their voting power, points and sets. Anyone can call it.
+    SyncReceipts{token_ids:Vec<String>},
+    /// Sent by the receipt collection after a receipt changed hands, moves its
+    /// position in the same transaction.
+    ReceiptTransferred{token_id:String},
+    /// Hands a position to `recipient` without unstaking. Its unclaimed
+    /// reward is settled with the current owner first, vesting if there is a
+    /// vesting policy.
+    TransferStake{token_id:String, recipient:String},
     DistributeReward{},
-    SetRewardWallet{address:String},
-    SetOwner{address:String},
-    SetStakingPeriod{time:u64},   
+    UpdateConfig(UpdateConfigMsg),
     WithdrawAllMoney{amount_juno:Uint128},
-    SetNftAddress{address:String},
-    SetTokenAddress{address:String},
-    SetStake{flag:bool},
-    SetDistributePeriod{time:u64}
+    /// Replaces the early-withdraw penalty, `None` removes it.
+    SetPenaltyPolicy{policy:Option<PenaltyPolicy>},
+    /// CW20 hook, the attached message is a `ReceiveMsg`.
+    Receive(Cw20ReceiveMsg),
+    /// Bonds the attached funds into the native pool of their denom.
+    BondNative{},
+    Unbond{pool:String, amount:Uint128},
+    WithdrawUnbonded{pool:String},
+    /// Vests like `GetReward`, unless `instant` takes it now less the haircut.
+    ClaimPoolRewards{pool:String, instant:Option<bool>},
+    SetPool{asset:PoolAsset, weight:u64},
+    /// Registers a contract to be sent a `StakeChangedHook`.
+    AddHook{addr:String},
+    RemoveHook{addr:String},
+    /// Spends loyalty points of `owner`, for the owner and registered spenders.
+    SpendPoints{owner:String, amount:Uint128, memo:Option<String>},
+    AddPointSpender{addr:String},
+    RemovePointSpender{addr:String},
+    /// Replaces the vesting of claimed rewards, `None` removes it.
+    SetVestingPolicy{policy:Option<VestingPolicy>},
+    /// Pays out every claim released so far.
+    WithdrawVested{},
+    /// Takes expired rewards of `token_ids` back into the next distribution,
+    /// the owner can send them to a treasury instead.
+    SweepExpiredRewards{token_ids:Vec<String>, destination:Option<PenaltyDestination>},
+    /// Opens a raffle with the attached funds as prizes, `commitment` is the
+    /// sha256 of a secret of at least 32 bytes.
+    CreateRaffle{commitment:HexBinary, draw_after:u64},
+    /// Records the drand signature of the raffle's `beacon_round` as its
+    /// seed, anyone can seal it once the round is out.
+    SealRaffle{raffle_id:u64, previous_signature:HexBinary, signature:HexBinary},
+    /// Checks `secret` against the commitment and pays out the prizes of a
+    /// sealed raffle. Past `reveal_deadline` the draw takes no secret.
+    DrawRaffle{raffle_id:u64, secret:Option<HexBinary>},
+    /// Only before `draw_after`.
+    CancelRaffle{raffle_id:u64},
+    /// A token set counts at once for whoever stakes all of it. A trait set
+    /// counts for an owner from their next stake, unstake or transfer.
+    AddCollectionSet{set:CollectionSet},
+    RemoveCollectionSet{name:String}
+}
+
+/// Attached to an NFT sent with `SendNft` by the contract owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ReceiveNftMsg {
+    RafflePrize{raffle_id:u64}
+}
+
+/// What a registered hook receives when a token is staked, unstaked or
+/// withdrawn. A status of `None` means the token is not in the contract. A
+/// position changing owner leaves the old owner and arrives with the new one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StakeChangedHookMsg {
+    pub owner: String,
+    pub token_id: String,
+    pub old_status: Option<String>,
+    pub new_status: Option<String>,
 }
 
+/// The execute message of a hook contract, `{"stake_changed_hook": {..}}`.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub enum StakeChangedExecuteMsg {
+    StakeChangedHook(StakeChangedHookMsg)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ReceiveMsg {
+    Bond{},
+    /// Escrows the tokens as a prize, only from the contract owner.
+    RafflePrize{raffle_id:u64}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum QueryMsg {
   GetStateInfo{},
   GetAllTokens{},
@@ -41,6 +182,240 @@ pub enum QueryMsg {
   GetCurrentTime{},
   GetToken{token_id:String},
   GetMyIds{address:String},
-  GetMyInfo{address:String}
+  GetMyInfo{address:String},
+  PendingRewards{address:String},
+  PoolStats{},
+  DistributionHistory{start_after:Option<u64>, limit:Option<u32>},
+  CarriedDust{},
+  PenaltyPolicy{},
+  PenaltyPreview{token_id:String},
+  Pools{},
+  Position{pool:String, address:String},
+  UnbondingQueue{address:String},
+  /// DAO voting module interface, `height` defaults to the current block.
+  VotingPowerAtHeight{address:String, height:Option<u64>},
+  TotalPowerAtHeight{height:Option<u64>},
+  Hooks{},
+  /// Owners that had at least `min_tokens` NFTs staked for `min_duration`
+  /// seconds at time `as_of`, paginated by owner.
+  StakerSnapshot{as_of:u64, min_tokens:u64, min_duration:u64, start_after:Option<String>, limit:Option<u32>},
+  Points{address:String},
+  /// Newest first, `start_after` is a spend id.
+  PointSpends{address:String, start_after:Option<u64>, limit:Option<u32>},
+  PointSpenders{},
+  VestingInfo{address:String},
+  ExpiringRewards{address:String},
+  Raffle{raffle_id:u64},
+  /// Newest first, `start_after` is a raffle id.
+  Raffles{start_after:Option<u64>, limit:Option<u32>},
+  CollectionSets{},
+  /// The sets `address` completes with their staked tokens.
+  MySets{address:String}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllTokensResponse {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllTokenInfoResponse {
+    pub tokens: Vec<TokenInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CurrentTimeResponse {
+    /// Block time in seconds.
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MyIdsResponse {
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MyInfoResponse {
+    pub tokens: Vec<TokenInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingRewardsResponse {
+    pub address: String,
+    pub token_count: u64,
+    /// Claimable rewards summed over every token the address has staked.
+    pub rewards: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolStatsResponse {
+    pub total_staked: Uint128,
+    /// Tokens that would share a distribution made right now.
+    pub eligible_count: u64,
+    pub last_distribute: u64,
+    pub last_distribute_amount: Uint128,
+    pub denom: String,
+    /// Rough yearly reward for one token, extrapolated from recent distributions.
+    pub annual_reward_per_nft: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DistributionHistoryResponse {
+    pub distributions: Vec<DistributionEpoch>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CarriedDustResponse {
+    pub amount: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PenaltyPolicyResponse {
+    /// `None` when no early-withdrawal penalty is configured.
+    pub policy: Option<PenaltyPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PenaltyPreviewResponse {
+    pub token_id: String,
+    /// Reward forfeited if the token were withdrawn at the current block time.
+    pub penalty: Uint128,
+    pub payout: Uint128,
+    pub penalty_free_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenResponse {
+    #[serde(flatten)]
+    pub info: TokenInfo,
+    /// Block time from which the token shares in distributions.
+    pub reward_eligible_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolsResponse {
+    pub nft_weight: u64,
+    pub pools: Vec<FungiblePool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingQueueResponse {
+    pub entries: Vec<UnbondingEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VotingPowerAtHeightResponse {
+    /// Tokens the address had staked, unstaking ones count as zero.
+    pub power: Uint128,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CollectionSetsResponse {
+    pub sets: Vec<CollectionSet>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MySetsResponse {
+    pub address: String,
+    pub sets: Vec<CompletedSet>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CompletedSet {
+    pub name: String,
+    pub bonus_percent: u64,
+    /// The staked tokens that make up the set and get the bonus.
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StakerSnapshotResponse {
+    pub stakers: Vec<StakerSnapshotEntry>,
+    /// Where the next page starts, `None` once every owner has been seen.
+    pub next_start_after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StakerSnapshotEntry {
+    pub owner: String,
+    /// Tokens that qualified, shorter stakes are left out.
+    pub token_count: u64,
+    /// Seconds staked summed over the qualifying tokens.
+    pub total_duration: u64,
+    pub longest_duration: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PointsResponse {
+    pub address: String,
+    /// Includes the points accrued up to the current block.
+    pub balance: Uint128,
+    pub staked_count: u64,
+    pub rate_per_token: u64,
+    /// Points the address currently earns per second.
+    pub rate: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PointSpendsResponse {
+    pub spends: Vec<PointSpend>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PointSpendersResponse {
+    pub spenders: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingInfoResponse {
+    pub address: String,
+    pub policy: Option<VestingPolicy>,
+    /// Released and not yet withdrawn.
+    pub withdrawable: Uint128,
+    /// Still to be released.
+    pub locked: Uint128,
+    pub tranches: Vec<VestingTranche>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExpiringRewardsResponse {
+    pub address: String,
+    /// Part of `rewards` that can already be swept.
+    pub expired: Uint128,
+    pub rewards: Vec<ExpiringReward>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExpiringReward {
+    pub token_id: String,
+    pub epoch: u64,
+    pub amount: Uint128,
+    /// `None` while rewards do not expire.
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RafflesResponse {
+    pub raffles: Vec<Raffle>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingEntry {
+    pub pool: String,
+    #[serde(flatten)]
+    pub locked: LockedAmount,
+    /// Whether `WithdrawUnbonded` would pay this entry out right now.
+    pub claimable: bool,
+}
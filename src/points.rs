@@ -0,0 +1,296 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, MessageInfo, Order, OverflowError, Response, StdResult, Storage, Uint128
+};
+use cw_storage_plus::Bound;
+
+use crate::contract::validate_address;
+use crate::error::ContractError;
+use crate::math::{checked_add, checked_sub};
+use crate::msg::{PointSpendersResponse, PointSpendsResponse, PointsResponse};
+use crate::state::{
+    PointSpend, PointsAccount, PointsIndex, CONFIG, POINTS, POINTS_INDEX, POINT_SPENDERS, POINT_SPENDS,
+    POINT_SPEND_COUNT, STAKED_POWER
+};
+
+const DEFAULT_SPENDS_LIMIT: u32 = 10;
+const MAX_SPENDS_LIMIT: u32 = 30;
+
+/// `index` brought forward to `now` at `rate` points per token per second.
+fn advanced_index(index: Option<PointsIndex>, rate: u64, now: u64) -> Result<PointsIndex, OverflowError> {
+    let index = match index {
+        Some(index) => index,
+        // points start accruing from the first change after they were introduced
+        None => return Ok(PointsIndex { per_token: Uint128::zero(), last_update: now }),
+    };
+    // an earlier block time leaves the index where it is instead of failing
+    // the stake or unstake that settles it
+    if now <= index.last_update {
+        return Ok(index);
+    }
+    let elapsed = checked_sub(now, index.last_update)?;
+    Ok(PointsIndex {
+        per_token: index.per_token.checked_add(Uint128::from(rate).checked_mul(Uint128::from(elapsed))?)?,
+        last_update: now,
+    })
+}
+
+/// `account` with the points earned by `staked` tokens since it was last settled.
+fn settled(account: Option<PointsAccount>, index: &PointsIndex, staked: Uint128) -> Result<PointsAccount, OverflowError> {
+    let account = account.unwrap_or_default();
+    let earned = index.per_token.checked_sub(account.per_token)?.checked_mul(staked)?;
+    Ok(PointsAccount {
+        balance: account.balance.checked_add(earned)?,
+        per_token: index.per_token,
+    })
+}
+
+/// Brings the accrual index up to `now`, the rate must not have changed since
+/// the last call. `execute_update_config` calls it before changing the rate.
+pub(crate) fn update_index(storage: &mut dyn Storage, now: u64) -> Result<PointsIndex, ContractError> {
+    let state = CONFIG.load(storage)?;
+    let index = advanced_index(POINTS_INDEX.may_load(storage)?, state.points_rate, now)?;
+    POINTS_INDEX.save(storage, &index)?;
+    Ok(index)
+}
+
+/// Credits `owner` with the points of their staked tokens up to `now`. Called
+/// before their number of "Staked" tokens changes, which `STAKED_POWER` tracks.
+pub(crate) fn accrue(storage: &mut dyn Storage, owner: &str, now: u64) -> Result<PointsAccount, ContractError> {
+    let index = update_index(storage, now)?;
+    let staked = STAKED_POWER.may_load(storage, owner)?.unwrap_or_default();
+    let account = settled(POINTS.may_load(storage, owner)?, &index, staked)?;
+    POINTS.save(storage, owner, &account)?;
+    Ok(account)
+}
+
+pub fn execute_add_point_spender(deps: DepsMut, info: MessageInfo, addr: String) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let addr = validate_address(deps.api, "spender", &addr)?;
+
+    let mut spenders = POINT_SPENDERS.may_load(deps.storage)?.unwrap_or_default();
+    if spenders.contains(&addr) {
+        return Err(ContractError::SpenderAlreadyRegistered {});
+    }
+    spenders.push(addr.clone());
+    POINT_SPENDERS.save(deps.storage, &spenders)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_point_spender")
+        .add_attribute("spender", addr))
+}
+
+pub fn execute_remove_point_spender(deps: DepsMut, info: MessageInfo, addr: String) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut spenders = POINT_SPENDERS.may_load(deps.storage)?.unwrap_or_default();
+    match spenders.iter().position(|spender| *spender == addr) {
+        Some(index) => spenders.remove(index),
+        None => return Err(ContractError::SpenderNotRegistered {}),
+    };
+    POINT_SPENDERS.save(deps.storage, &spenders)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_point_spender")
+        .add_attribute("spender", addr))
+}
+
+/// Points can not be transferred, only spent on behalf of their owner by the
+/// contract owner or a registered spender such as a shop.
+pub fn execute_spend_points(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    amount: Uint128,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let spenders = POINT_SPENDERS.may_load(deps.storage)?.unwrap_or_default();
+
+    if info.sender != state.owner && !spenders.contains(&info.sender.to_string()) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let now = env.block.time.seconds();
+    let mut account = accrue(deps.storage, &owner, now)?;
+    if account.balance < amount {
+        return Err(ContractError::InsufficientPoints { balance: account.balance, amount });
+    }
+    account.balance = account.balance.checked_sub(amount)?;
+    POINTS.save(deps.storage, &owner, &account)?;
+
+    let id = checked_add(POINT_SPEND_COUNT.may_load(deps.storage)?.unwrap_or_default(), 1)?;
+    POINT_SPEND_COUNT.save(deps.storage, &id)?;
+    POINT_SPENDS.save(deps.storage, (&owner, id), &PointSpend {
+        id,
+        spender: info.sender.to_string(),
+        amount,
+        time: now,
+        memo,
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "spend_points")
+        .add_attribute("owner", owner)
+        .add_attribute("spender", info.sender)
+        .add_attribute("amount", amount)
+        .add_attribute("balance", account.balance))
+}
+
+pub fn query_points(deps: Deps, env: Env, address: String) -> StdResult<PointsResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let index = advanced_index(POINTS_INDEX.may_load(deps.storage)?, state.points_rate, env.block.time.seconds())?;
+    let staked = STAKED_POWER.may_load(deps.storage, &address)?.unwrap_or_default();
+    let account = settled(POINTS.may_load(deps.storage, &address)?, &index, staked)?;
+
+    Ok(PointsResponse {
+        address,
+        balance: account.balance,
+        staked_count: staked.u128() as u64,
+        rate_per_token: state.points_rate,
+        rate: Uint128::from(state.points_rate).checked_mul(staked)?,
+    })
+}
+
+/// Spends of `address`, newest first.
+pub fn query_point_spends(
+    deps: Deps,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PointSpendsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_SPENDS_LIMIT).min(MAX_SPENDS_LIMIT) as usize;
+    let end = start_after.map(Bound::exclusive_int);
+
+    let spends = POINT_SPENDS
+        .prefix(&address)
+        .range(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|(_, spend)| spend))
+        .collect::<StdResult<_>>()?;
+
+    Ok(PointSpendsResponse { spends })
+}
+
+pub fn query_point_spenders(deps: Deps) -> StdResult<PointSpendersResponse> {
+    Ok(PointSpendersResponse {
+        spenders: POINT_SPENDERS.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InstantiateMsg, UpdateConfigMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{to_json_binary, DepsMut};
+    use cw721::Cw721ReceiveMsg;
+
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn stake(deps: DepsMut, owner: &str, token_id: &str) {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+    }
+
+    fn balance(deps: Deps, seconds: u64, address: &str) -> u128 {
+        query_points(deps, later(seconds), address.to_string()).unwrap().balance.u128()
+    }
+
+    #[test]
+    fn points_accrue_while_staked() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { points_rate: Some(10), ..instantiate_msg() };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        stake(deps.as_mut(), "owner1", "1");
+        stake(deps.as_mut(), "owner1", "2");
+
+        let points = query_points(deps.as_ref(), later(100), "owner1".to_string()).unwrap();
+        assert_eq!(points.balance, Uint128::new(2000));
+        assert_eq!((points.staked_count, points.rate), (2, Uint128::new(20)));
+
+        // unstaking stops one token, a transfer hands the other to owner2
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+        let msg = ExecuteMsg::TransferStake { token_id: "2".to_string(), recipient: "owner2".to_string() };
+        execute(deps.as_mut(), later(200), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(balance(deps.as_ref(), 300, "owner1"), 3000);
+        assert_eq!(balance(deps.as_ref(), 300, "owner2"), 1000);
+
+        // a new rate only applies from when it is set
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { points_rate: Some(20), ..UpdateConfigMsg::default() });
+        execute(deps.as_mut(), later(300), mock_info("creator", &[]), msg).unwrap();
+        assert_eq!(balance(deps.as_ref(), 400, "owner2"), 3000);
+
+        // points outlive the token
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), later(1100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(balance(deps.as_ref(), 2000, "owner1"), 3000);
+    }
+
+    #[test]
+    fn spenders_spend_points() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { points_rate: Some(10), ..instantiate_msg() };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        stake(deps.as_mut(), "owner1", "1");
+
+        let spend = |amount: u128, memo: &str| ExecuteMsg::SpendPoints {
+            owner: "owner1".to_string(),
+            amount: Uint128::new(amount),
+            memo: Some(memo.to_string()),
+        };
+        let err = execute(deps.as_mut(), later(100), mock_info("shop", &[]), spend(100, "hat")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), spend(100, "hat")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let msg = ExecuteMsg::AddPointSpender { addr: "shop".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::SpenderAlreadyRegistered {}));
+        assert_eq!(query_point_spenders(deps.as_ref()).unwrap().spenders, ["shop"]);
+
+        execute(deps.as_mut(), later(100), mock_info("shop", &[]), spend(600, "hat")).unwrap();
+        execute(deps.as_mut(), later(100), mock_info("creator", &[]), spend(300, "refund")).unwrap();
+        let err = execute(deps.as_mut(), later(100), mock_info("shop", &[]), spend(101, "cape")).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientPoints { balance, .. } if balance == Uint128::new(100)
+        ));
+        assert_eq!(balance(deps.as_ref(), 150, "owner1"), 600);
+
+        let spends = query_point_spends(deps.as_ref(), "owner1".to_string(), None, None).unwrap().spends;
+        let summary: Vec<_> = spends.iter().map(|spend| (spend.id, spend.spender.as_str(), spend.amount.u128())).collect();
+        assert_eq!(summary, [(2, "creator", 300), (1, "shop", 600)]);
+        assert_eq!(spends[1].memo.as_deref(), Some("hat"));
+        let spends = query_point_spends(deps.as_ref(), "owner1".to_string(), Some(2), None).unwrap().spends;
+        assert_eq!(spends.len(), 1);
+        assert!(query_point_spends(deps.as_ref(), "owner2".to_string(), None, None).unwrap().spends.is_empty());
+
+        let msg = ExecuteMsg::RemovePointSpender { addr: "shop".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::SpenderNotRegistered {}));
+        let err = execute(deps.as_mut(), later(200), mock_info("shop", &[]), spend(100, "hat")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+}
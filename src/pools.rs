@@ -0,0 +1,592 @@
+use cosmwasm_std::{
+    from_json, to_json_binary, BankMsg, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Order, OverflowError,
+    Response, StdResult, Storage, Uint128, WasmMsg
+};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+
+use crate::contract::{validate_address, validate_denom};
+use crate::error::ContractError;
+use crate::math::{checked_add, checked_multiply_ratio};
+use crate::{raffle, vesting};
+use crate::msg::{PoolsResponse, ReceiveMsg, UnbondingEntry, UnbondingQueueResponse};
+use crate::state::{
+    FungiblePool, LockedAmount, PoolAsset, RafflePrize, StakePosition, State, CONFIG, POOLS, POSITIONS
+};
+
+/// Registers a CW20 LP token or a native denom as a pool, or changes the
+/// emission weight of an existing one. A weight of zero stops the pool from
+/// sharing distributions.
+pub fn execute_set_pool(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: PoolAsset,
+    weight: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    // the validated address is kept, `Receive` compares the sender against it
+    let asset = match asset {
+        PoolAsset::Cw20 { address } => PoolAsset::Cw20 { address: validate_address(deps.api, "pool token", &address)? },
+        // bonded funds would be indistinguishable from the reward budget
+        PoolAsset::Native { denom } if denom == state.denom => {
+            return Err(ContractError::RewardDenomPool {});
+        }
+        PoolAsset::Native { denom } => {
+            validate_denom(&denom)?;
+            PoolAsset::Native { denom }
+        }
+    };
+
+    let pool_id = asset.id().to_string();
+    POOLS.update(deps.storage, &pool_id, |pool| -> Result<_, ContractError> {
+        let mut pool = pool.unwrap_or(FungiblePool {
+            id: pool_id.clone(),
+            asset: asset.clone(),
+            weight: 0,
+            total_bonded: Uint128::zero(),
+        });
+        if pool.asset != asset {
+            return Err(ContractError::UnknownPool { pool: pool_id.clone() });
+        }
+        pool.weight = weight;
+        Ok(pool)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_pool")
+        .add_attribute("pool", pool_id)
+        .add_attribute("weight", weight.to_string()))
+}
+
+/// Entry point of the CW20 `Send` hook. The sending contract is the LP token.
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match from_json(&wrapper.msg)? {
+        ReceiveMsg::Bond {} => {
+            let owner = validate_address(deps.api, "sender", &wrapper.sender)?;
+            let asset = PoolAsset::Cw20 { address: info.sender.to_string() };
+            execute_bond(deps, env, asset, owner, wrapper.amount)
+        }
+        ReceiveMsg::RafflePrize { raffle_id } => {
+            let prize = RafflePrize::Cw20 { address: info.sender.to_string(), amount: wrapper.amount };
+            raffle::add_prize(deps.storage, &wrapper.sender, raffle_id, prize)
+        }
+    }
+}
+
+pub fn execute_bond_native(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let coin = match info.funds.as_slice() {
+        [coin] => coin,
+        _ => return Err(ContractError::InvalidFunds {}),
+    };
+    let asset = PoolAsset::Native { denom: coin.denom.clone() };
+    execute_bond(deps, env, asset, info.sender.to_string(), coin.amount)
+}
+
+fn execute_bond(
+    deps: DepsMut,
+    env: Env,
+    asset: PoolAsset,
+    owner: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if !state.can_stake {
+        return Err(ContractError::CanNotStake {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let pool_id = asset.id().to_string();
+    let mut pool = match POOLS.may_load(deps.storage, &pool_id)? {
+        Some(pool) if pool.asset == asset => pool,
+        _ => return Err(ContractError::UnknownPool { pool: pool_id }),
+    };
+
+    let mut position = POSITIONS
+        .may_load(deps.storage, (&pool_id, &owner))?
+        .unwrap_or_else(|| StakePosition::new(&pool_id, &owner));
+    position.warming.retain(|entry| entry.until > env.block.time.seconds());
+    position.bonded = position.bonded.checked_add(amount)?;
+    if state.min_stake_age > 0 {
+        position.warming.push(LockedAmount {
+            amount,
+            until: checked_add(env.block.time.seconds(), state.min_stake_age)?,
+            warm_until: None,
+        });
+    }
+    POSITIONS.save(deps.storage, (&pool_id, &owner), &position)?;
+
+    pool.total_bonded = pool.total_bonded.checked_add(amount)?;
+    POOLS.save(deps.storage, &pool_id, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "bond")
+        .add_attribute("pool", pool_id)
+        .add_attribute("owner", owner)
+        .add_attribute("amount", amount))
+}
+
+/// Starts the `staking_period` cooldown for part of a position. Like an
+/// unstaking NFT, the amount keeps earning until the cooldown is over.
+pub fn execute_unbond(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let owner = info.sender.to_string();
+
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let mut position = match POSITIONS.may_load(deps.storage, (&pool_id, &owner))? {
+        Some(position) => position,
+        None => return Err(ContractError::NotStaked {}),
+    };
+    if amount > position.bonded {
+        return Err(ContractError::Notenough {});
+    }
+
+    position.bonded = position.bonded.checked_sub(amount)?;
+    let until = checked_add(env.block.time.seconds(), state.staking_period)?;
+    // deposits still warming up are the first to leave, and keep warming up
+    position.warming.retain(|entry| entry.until > env.block.time.seconds());
+    let mut remaining = amount;
+    while let Some(entry) = position.warming.last_mut() {
+        if remaining.is_zero() {
+            break;
+        }
+        let part = entry.amount.min(remaining);
+        position.unbonding.push(LockedAmount { amount: part, until, warm_until: Some(entry.until) });
+        entry.amount = entry.amount.checked_sub(part)?;
+        remaining = remaining.checked_sub(part)?;
+        if entry.amount.is_zero() {
+            position.warming.pop();
+        }
+    }
+    if !remaining.is_zero() {
+        position.unbonding.push(LockedAmount { amount: remaining, until, warm_until: None });
+    }
+    POSITIONS.save(deps.storage, (&pool_id, &owner), &position)?;
+
+    POOLS.update(deps.storage, &pool_id, |pool| -> Result<_, ContractError> {
+        let mut pool = pool.ok_or(ContractError::UnknownPool { pool: pool_id.clone() })?;
+        pool.total_bonded = pool.total_bonded.checked_sub(amount)?;
+        Ok(pool)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unbond")
+        .add_attribute("pool", pool_id)
+        .add_attribute("amount", amount))
+}
+
+/// Pays out every unbonding entry whose cooldown is over. Once nothing is left
+/// in the position its remaining reward is settled as well and it is closed.
+pub fn execute_withdraw_unbonded(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let owner = info.sender.to_string();
+    let now = env.block.time.seconds();
+
+    let pool = match POOLS.may_load(deps.storage, &pool_id)? {
+        Some(pool) => pool,
+        None => return Err(ContractError::UnknownPool { pool: pool_id }),
+    };
+    let mut position = match POSITIONS.may_load(deps.storage, (&pool_id, &owner))? {
+        Some(position) => position,
+        None => return Err(ContractError::NotStaked {}),
+    };
+
+    let (matured, pending): (Vec<LockedAmount>, Vec<LockedAmount>) =
+        position.unbonding.into_iter().partition(|entry| entry.until <= now);
+    position.unbonding = pending;
+    let amount = sum_locked(matured.iter())?;
+    if amount.is_zero() {
+        return Err(ContractError::TimeRemaining {});
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![send_asset(&pool.asset, &owner, amount)?];
+
+    if position.bonded.is_zero() && position.unbonding.is_empty() {
+        if !position.reward_juno.is_zero() {
+            messages.extend(vesting::settle_claim(deps.storage, &state, &owner, position.reward_juno, false, now)?);
+        }
+        POSITIONS.remove(deps.storage, (&pool_id, &owner));
+    } else {
+        POSITIONS.save(deps.storage, (&pool_id, &owner), &position)?;
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "withdraw_unbonded")
+        .add_attribute("pool", pool_id)
+        .add_attribute("amount", amount))
+}
+
+/// Settled under the vesting policy like `GetReward`.
+pub fn execute_claim_pool_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    instant: bool,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let owner = info.sender.to_string();
+
+    let mut position = match POSITIONS.may_load(deps.storage, (&pool_id, &owner))? {
+        Some(position) => position,
+        None => return Err(ContractError::NotStaked {}),
+    };
+    let reward = position.reward_juno;
+    position.reward_juno = Uint128::zero();
+    POSITIONS.save(deps.storage, (&pool_id, &owner), &position)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "claim_pool_rewards")
+        .add_attribute("pool", pool_id)
+        .add_attribute("amount", reward);
+    if !reward.is_zero() {
+        let messages = vesting::settle_claim(deps.storage, &state, &owner, reward, instant, env.block.time.seconds())?;
+        response = response.add_messages(messages);
+    }
+    Ok(response)
+}
+
+fn send_asset(asset: &PoolAsset, recipient: &str, amount: Uint128) -> StdResult<CosmosMsg> {
+    Ok(match asset {
+        PoolAsset::Cw20 { address } => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.clone(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+        PoolAsset::Native { denom } => CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: denom.clone(),
+                amount,
+            }],
+        }),
+    })
+}
+
+/// Part of a position that shares in a distribution made at `now`.
+pub fn eligible_amount(position: &StakePosition, now: u64) -> Result<Uint128, OverflowError> {
+    let warming = sum_locked(position.warming.iter().filter(|entry| entry.until > now))?;
+    let unbonding = sum_locked(
+        position
+            .unbonding
+            .iter()
+            .filter(|entry| entry.until > now && entry.warm_until.is_none_or(|warm_until| warm_until <= now)),
+    )?;
+    position.bonded.checked_sub(warming)?.checked_add(unbonding)
+}
+
+fn sum_locked<'a>(mut entries: impl Iterator<Item = &'a LockedAmount>) -> Result<Uint128, OverflowError> {
+    entries.try_fold(Uint128::zero(), |total, entry| total.checked_add(entry.amount))
+}
+
+/// Pools with a non-zero weight and something eligible, with that amount.
+pub fn eligible_pools(storage: &dyn Storage, now: u64) -> Result<Vec<(FungiblePool, Uint128)>, ContractError> {
+    let mut eligible = vec![];
+    for item in POOLS.range(storage, None, None, Order::Ascending) {
+        let (pool_id, pool) = item?;
+        if pool.weight == 0 {
+            continue;
+        }
+        let mut total = Uint128::zero();
+        for item in POSITIONS.prefix(&pool_id).range(storage, None, None, Order::Ascending) {
+            let (_, position) = item?;
+            total = total.checked_add(eligible_amount(&position, now)?)?;
+        }
+        if !total.is_zero() {
+            eligible.push((pool, total));
+        }
+    }
+    Ok(eligible)
+}
+
+/// Shares `amount` among the positions of a pool in proportion to their
+/// eligible amount and returns how much was actually credited.
+pub fn credit_pool(
+    storage: &mut dyn Storage,
+    pool_id: &str,
+    amount: Uint128,
+    eligible_total: Uint128,
+    now: u64,
+) -> Result<Uint128, ContractError> {
+    let positions: Vec<(String, StakePosition)> = POSITIONS
+        .prefix(pool_id)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let mut credited = Uint128::zero();
+    for (owner, mut position) in positions {
+        let share = checked_multiply_ratio(amount, eligible_amount(&position, now)?, eligible_total)?;
+        if share.is_zero() {
+            continue;
+        }
+        position.reward_juno = position.reward_juno.checked_add(share)?;
+        credited = credited.checked_add(share)?;
+        POSITIONS.save(storage, (pool_id, &owner), &position)?;
+    }
+    Ok(credited)
+}
+
+/// Unclaimed pool rewards of `owner` summed over every pool.
+pub fn pending_pool_rewards(storage: &dyn Storage, owner: &str) -> StdResult<Uint128> {
+    let pool_ids: Vec<String> = POOLS
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    let mut total = Uint128::zero();
+    for pool_id in pool_ids {
+        if let Some(position) = POSITIONS.may_load(storage, (&pool_id, owner))? {
+            total = total.checked_add(position.reward_juno)?;
+        }
+    }
+    Ok(total)
+}
+
+pub fn query_pools(deps: Deps) -> StdResult<PoolsResponse> {
+    let state: State = CONFIG.load(deps.storage)?;
+    let pools = POOLS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, pool)| pool))
+        .collect::<StdResult<_>>()?;
+    Ok(PoolsResponse {
+        nft_weight: state.nft_weight,
+        pools,
+    })
+}
+
+pub fn query_position(deps: Deps, pool_id: String, address: String) -> StdResult<StakePosition> {
+    Ok(POSITIONS
+        .may_load(deps.storage, (&pool_id, &address))?
+        .unwrap_or_else(|| StakePosition::new(&pool_id, &address)))
+}
+
+/// Every pending unbonding of `address` across all pools, oldest first.
+pub fn query_unbonding_queue(deps: Deps, env: Env, address: String) -> StdResult<UnbondingQueueResponse> {
+    let now = env.block.time.seconds();
+    let pool_ids: Vec<String> = POOLS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let mut entries = vec![];
+    for pool_id in pool_ids {
+        if let Some(position) = POSITIONS.may_load(deps.storage, (&pool_id, &address))? {
+            entries.extend(position.unbonding.into_iter().map(|locked| UnbondingEntry {
+                pool: pool_id.clone(),
+                claimable: locked.until <= now,
+                locked,
+            }));
+        }
+    }
+    entries.sort_by_key(|entry| entry.locked.until);
+    Ok(UnbondingQueueResponse { entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate, query_distribution_history, query_pending_rewards};
+    use crate::contract::tests::instantiate_msg;
+    use crate::msg::{ExecuteMsg, UpdateConfigMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cw721::Cw721ReceiveMsg;
+
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn bond(deps: DepsMut, env: Env, lp_token: &str, owner: &str, amount: u128) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: owner.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_json_binary(&ReceiveMsg::Bond {}).unwrap(),
+        });
+        execute(deps, env, mock_info(lp_token, &[]), msg)
+    }
+
+    fn distribute(deps: DepsMut, env: Env, amount: u128) {
+        let info = mock_info("reward_wallet", &[Coin { denom: "ujuno".to_string(), amount: Uint128::new(amount) }]);
+        execute(deps, env, info, ExecuteMsg::DistributeReward {}).unwrap();
+    }
+
+    #[test]
+    fn lp_pools_share_distributions_by_weight() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let err = bond(deps.as_mut(), mock_env(), "lp_token", "owner1", 10).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownPool { .. }));
+
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Cw20 { address: "lp_token".to_string() }, weight: 50 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let mixed_case = ExecuteMsg::SetPool { asset: PoolAsset::Cw20 { address: "LP_Token".to_string() }, weight: 50 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), mixed_case).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { nft_weight: Some(50), ..UpdateConfigMsg::default() });
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "owner1".to_string(),
+            token_id: "reveal1".to_string(),
+            msg: to_json_binary(&"abc".to_string()).unwrap()
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+        bond(deps.as_mut(), mock_env(), "lp_token", "owner1", 300).unwrap();
+        bond(deps.as_mut(), mock_env(), "lp_token", "owner2", 100).unwrap();
+
+        let pools = query_pools(deps.as_ref()).unwrap();
+        assert_eq!(pools.nft_weight, 50);
+        assert_eq!(pools.pools[0].total_bonded, Uint128::new(400));
+
+        // half to the NFT, the other half split 3:1 between the LP holders
+        distribute(deps.as_mut(), later(100), 800);
+        assert_eq!(query_position(deps.as_ref(), "lp_token".to_string(), "owner1".to_string()).unwrap().reward_juno, Uint128::new(300));
+        assert_eq!(query_position(deps.as_ref(), "lp_token".to_string(), "owner2".to_string()).unwrap().reward_juno, Uint128::new(100));
+        let pending = query_pending_rewards(deps.as_ref(), "owner1".to_string()).unwrap();
+        assert_eq!(pending.rewards[0].amount, Uint128::new(700));
+        let epoch = &query_distribution_history(deps.as_ref(), None, None).unwrap().distributions[0];
+        assert_eq!(epoch.pool_rewards, Uint128::new(400));
+        assert_eq!(epoch.dust, Uint128::zero());
+
+        // unbonding amounts keep earning through the cooldown
+        let msg = ExecuteMsg::Unbond { pool: "lp_token".to_string(), amount: Uint128::new(100) };
+        execute(deps.as_mut(), later(100), mock_info("owner2", &[]), msg).unwrap();
+        let msg = ExecuteMsg::WithdrawUnbonded { pool: "lp_token".to_string() };
+        let err = execute(deps.as_mut(), later(200), mock_info("owner2", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::TimeRemaining {}));
+        distribute(deps.as_mut(), later(200), 800);
+        assert_eq!(query_position(deps.as_ref(), "lp_token".to_string(), "owner2".to_string()).unwrap().reward_juno, Uint128::new(200));
+
+        // the matured LP and the leftover reward go back to the owner
+        let res = execute(deps.as_mut(), later(1100), mock_info("owner2", &[]), msg).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "lp_token".to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer { recipient: "owner2".to_string(), amount: Uint128::new(100) }).unwrap(),
+            funds: vec![],
+        }));
+        assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner2".to_string(),
+            amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(200) }]
+        }));
+        assert_eq!(query_position(deps.as_ref(), "lp_token".to_string(), "owner2".to_string()).unwrap().bonded, Uint128::zero());
+
+        let msg = ExecuteMsg::ClaimPoolRewards { pool: "lp_token".to_string(), instant: None };
+        let res = execute(deps.as_mut(), later(1100), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner1".to_string(),
+            amount: vec![Coin { denom: "ujuno".to_string(), amount: Uint128::new(600) }]
+        }));
+    }
+
+    #[test]
+    fn warming_deposits_do_not_earn() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Cw20 { address: "lp_token".to_string() }, weight: 1 };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg { min_stake_age: Some(500), ..UpdateConfigMsg::default() });
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        bond(deps.as_mut(), mock_env(), "lp_token", "owner1", 100).unwrap();
+        bond(deps.as_mut(), later(400), "lp_token", "owner2", 100).unwrap();
+
+        distribute(deps.as_mut(), later(500), 100);
+        assert_eq!(query_position(deps.as_ref(), "lp_token".to_string(), "owner1".to_string()).unwrap().reward_juno, Uint128::new(100));
+        assert_eq!(query_position(deps.as_ref(), "lp_token".to_string(), "owner2".to_string()).unwrap().reward_juno, Uint128::zero());
+
+        // unbonding takes the warming deposit first
+        bond(deps.as_mut(), later(500), "lp_token", "owner1", 50).unwrap();
+        let msg = ExecuteMsg::Unbond { pool: "lp_token".to_string(), amount: Uint128::new(50) };
+        execute(deps.as_mut(), later(500), mock_info("owner1", &[]), msg).unwrap();
+        let position = query_position(deps.as_ref(), "lp_token".to_string(), "owner1".to_string()).unwrap();
+        assert!(position.warming.is_empty());
+        // and earn nothing during the cooldown before their warm up is over
+        assert_eq!(eligible_amount(&position, later(600).block.time.seconds()).unwrap(), Uint128::new(100));
+        assert_eq!(eligible_amount(&position, later(1000).block.time.seconds()).unwrap(), Uint128::new(150));
+    }
+
+    #[test]
+    fn native_pool_unbonding_queue() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Native { denom: "ujuno".to_string() }, weight: 1 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::RewardDenomPool {}));
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Native { denom: "ugov".to_string() }, weight: 1 };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), ExecuteMsg::BondNative {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFunds {}));
+        let funds = [Coin { denom: "uatom".to_string(), amount: Uint128::new(100) }];
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &funds), ExecuteMsg::BondNative {}).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownPool { .. }));
+        // a CW20 contract can not bond into a native pool through the hook
+        let err = bond(deps.as_mut(), mock_env(), "ugov", "owner1", 100).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownPool { .. }));
+
+        let funds = [Coin { denom: "ugov".to_string(), amount: Uint128::new(300) }];
+        execute(deps.as_mut(), mock_env(), mock_info("owner1", &funds), ExecuteMsg::BondNative {}).unwrap();
+
+        // several unbondings, each with its own release time
+        let msg = ExecuteMsg::Unbond { pool: "ugov".to_string(), amount: Uint128::new(100) };
+        execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg.clone()).unwrap();
+        execute(deps.as_mut(), later(500), mock_info("owner1", &[]), msg).unwrap();
+
+        let queue = query_unbonding_queue(deps.as_ref(), later(1200), "owner1".to_string()).unwrap();
+        assert_eq!(queue.entries, vec![
+            UnbondingEntry {
+                pool: "ugov".to_string(),
+                locked: LockedAmount { amount: Uint128::new(100), until: later(1100).block.time.seconds(), warm_until: None },
+                claimable: true,
+            },
+            UnbondingEntry {
+                pool: "ugov".to_string(),
+                locked: LockedAmount { amount: Uint128::new(100), until: later(1500).block.time.seconds(), warm_until: None },
+                claimable: false,
+            },
+        ]);
+
+        let msg = ExecuteMsg::WithdrawUnbonded { pool: "ugov".to_string() };
+        let res = execute(deps.as_mut(), later(1200), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner1".to_string(),
+            amount: vec![Coin { denom: "ugov".to_string(), amount: Uint128::new(100) }]
+        }));
+        let position = query_position(deps.as_ref(), "ugov".to_string(), "owner1".to_string()).unwrap();
+        assert_eq!(position.bonded, Uint128::new(100));
+        assert_eq!(position.unbonding.len(), 1);
+    }
+}
@@ -0,0 +1,500 @@
+use cosmwasm_std::{
+    to_json_binary, BankMsg, Coin, CosmosMsg, Deps, DepsMut, Env, HexBinary, MessageInfo, Order, Response,
+    StdResult, Storage, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use cw721::Cw721ExecuteMsg;
+use cw_storage_plus::Bound;
+use sha2::{Digest, Sha256};
+
+use crate::beacon;
+use crate::error::ContractError;
+use crate::math::checked_add;
+use crate::msg::RafflesResponse;
+use crate::receipts;
+use crate::state::{
+    Raffle, RafflePrize, RaffleStatus, RaffleWinner, State, CONFIG, RAFFLES, RAFFLE_COUNT, RAFFLE_TICKETS, TOKENINFO,
+};
+
+const DEFAULT_RAFFLES_LIMIT: u32 = 10;
+const MAX_RAFFLES_LIMIT: u32 = 30;
+/// Shortest secret accepted on reveal, so it can not be guessed from the commitment.
+const MIN_SECRET_BYTES: usize = 32;
+/// How long after `draw_after` the owner has to reveal the secret.
+const REVEAL_WINDOW: u64 = 24 * 60 * 60;
+
+/// Opens a raffle committed to `sha256(secret)` with a ticket for every token
+/// staked right now. Funds sent along are its first prizes, CW20 and cw721
+/// prizes are added by sending them with a `RafflePrize` hook message.
+pub fn execute_create_raffle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    commitment: HexBinary,
+    draw_after: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if commitment.len() != 32 {
+        return Err(ContractError::InvalidCommitment {});
+    }
+    if draw_after <= env.block.time.seconds() {
+        return Err(ContractError::InvalidDrawTime {});
+    }
+
+    let id = checked_add(RAFFLE_COUNT.may_load(deps.storage)?.unwrap_or_default(), 1)?;
+    RAFFLE_COUNT.save(deps.storage, &id)?;
+    let prizes = info
+        .funds
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .map(|coin| RafflePrize::Native { denom: coin.denom, amount: coin.amount })
+        .collect();
+    let tickets = tickets(deps.storage)?;
+    RAFFLE_TICKETS.save(deps.storage, id, &tickets)?;
+    RAFFLES.save(deps.storage, id, &Raffle {
+        id,
+        commitment,
+        created_at: env.block.time.seconds(),
+        draw_after,
+        beacon_round: beacon::round_after(draw_after),
+        reveal_deadline: checked_add(draw_after, REVEAL_WINDOW)?,
+        seed: None,
+        prizes,
+        status: RaffleStatus::Open,
+        winners: vec![],
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_raffle")
+        .add_attribute("raffle_id", id.to_string())
+        .add_attribute("tickets", tickets.len().to_string()))
+}
+
+/// Escrows a CW20 or cw721 prize the contract owner sent with a hook message.
+pub(crate) fn add_prize(
+    storage: &mut dyn Storage,
+    sender: &str,
+    raffle_id: u64,
+    prize: RafflePrize,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(storage)?;
+
+    if sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut raffle = load_open(storage, raffle_id)?;
+    raffle.prizes.push(prize);
+    RAFFLES.save(storage, raffle_id, &raffle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_raffle_prize")
+        .add_attribute("raffle_id", raffle_id.to_string()))
+}
+
+fn load_open(storage: &dyn Storage, raffle_id: u64) -> Result<Raffle, ContractError> {
+    let raffle = match RAFFLES.may_load(storage, raffle_id)? {
+        Some(raffle) => raffle,
+        None => return Err(ContractError::UnknownRaffle { raffle_id }),
+    };
+    if raffle.status != RaffleStatus::Open {
+        return Err(ContractError::RaffleClosed { raffle_id });
+    }
+    Ok(raffle)
+}
+
+/// Every "Staked" token in token id order. Taken when the raffle opens, so
+/// staking or unstaking afterwards does not change who can win.
+fn tickets(storage: &dyn Storage) -> StdResult<Vec<(String, String)>> {
+    TOKENINFO
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, token)) => token.status == "Staked",
+            Err(_) => true,
+        })
+        .map(|item| item.map(|(token_id, token)| (token_id, token.owner)))
+        .collect()
+}
+
+/// Index of the ticket drawn for prize `draw` out of `count`.
+fn draw_index(secret: &[u8], seed: &[u8], raffle_id: u64, draw: u64, count: usize) -> usize {
+    let hash = Sha256::new()
+        .chain_update(secret)
+        .chain_update(seed)
+        .chain_update(raffle_id.to_be_bytes())
+        .chain_update(draw.to_be_bytes())
+        .finalize();
+    let mut word = [0u8; 16];
+    word.copy_from_slice(&hash[..16]);
+    (u128::from_be_bytes(word) % count as u128) as usize
+}
+
+fn send_prize(prize: &RafflePrize, recipient: &str) -> StdResult<CosmosMsg> {
+    Ok(match prize {
+        RafflePrize::Native { denom, amount } => CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin { denom: denom.clone(), amount: *amount }],
+        }),
+        RafflePrize::Cw20 { address, amount } => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.clone(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer { recipient: recipient.to_string(), amount: *amount })?,
+            funds: vec![],
+        }),
+        RafflePrize::Cw721 { address, token_id } => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.clone(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: recipient.to_string(),
+                token_id: token_id.clone(),
+            })?,
+            funds: vec![],
+        }),
+    })
+}
+
+/// Records the randomness of the raffle's beacon round as its seed. The round
+/// only exists after `draw_after`, anyone can seal with it, and after that the
+/// raffle can only be drawn.
+pub fn execute_seal_raffle(
+    deps: DepsMut,
+    raffle_id: u64,
+    previous_signature: HexBinary,
+    signature: HexBinary,
+) -> Result<Response, ContractError> {
+    let mut raffle = load_open(deps.storage, raffle_id)?;
+
+    if !beacon::verify(raffle.beacon_round, &previous_signature, &signature) {
+        return Err(ContractError::InvalidBeacon { round: raffle.beacon_round });
+    }
+    raffle.seed = Some(HexBinary::from(beacon::randomness(&signature)));
+    raffle.status = RaffleStatus::Sealed;
+    RAFFLES.save(deps.storage, raffle_id, &raffle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "seal_raffle")
+        .add_attribute("raffle_id", raffle_id.to_string()))
+}
+
+/// The holder of a ticket's position at the draw, or the owner when the
+/// raffle opened if the token has been withdrawn since.
+fn ticket_holder(deps: Deps, state: &State, raffle: &Raffle, token_id: &str, owner: String) -> StdResult<String> {
+    match TOKENINFO.may_load(deps.storage, token_id)? {
+        Some(token) if token.stake_time <= raffle.created_at => {
+            receipts::position_holder(&deps.querier, state, token_id, token.owner)
+        }
+        _ => Ok(owner),
+    }
+}
+
+/// Reveals the secret of a sealed raffle and pays its prizes out. Anyone
+/// holding the secret can draw, and past `reveal_deadline` anyone can draw
+/// without it. Each prize goes to a different ticket, prizes left without a
+/// ticket go back to the owner.
+pub fn execute_draw_raffle(
+    deps: DepsMut,
+    env: Env,
+    raffle_id: u64,
+    secret: Option<HexBinary>,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let mut raffle = match RAFFLES.may_load(deps.storage, raffle_id)? {
+        Some(raffle) => raffle,
+        None => return Err(ContractError::UnknownRaffle { raffle_id }),
+    };
+    let seed = match (&raffle.status, &raffle.seed) {
+        (RaffleStatus::Sealed, Some(seed)) => seed.clone(),
+        (RaffleStatus::Open, _) => return Err(ContractError::RaffleNotSealed { raffle_id }),
+        _ => return Err(ContractError::RaffleClosed { raffle_id }),
+    };
+    // past the deadline the seed alone decides, whoever draws
+    let secret = if env.block.time.seconds() >= raffle.reveal_deadline {
+        HexBinary::default()
+    } else {
+        let secret = match secret {
+            Some(secret) => secret,
+            None => return Err(ContractError::RevealPending { raffle_id, deadline: raffle.reveal_deadline }),
+        };
+        if secret.len() < MIN_SECRET_BYTES || Sha256::digest(secret.as_slice())[..] != raffle.commitment[..] {
+            return Err(ContractError::WrongSecret {});
+        }
+        secret
+    };
+
+    let mut tickets = RAFFLE_TICKETS.load(deps.storage, raffle_id)?;
+    let mut messages = vec![];
+    for (draw, prize) in raffle.prizes.iter().enumerate() {
+        if tickets.is_empty() {
+            messages.push(send_prize(prize, &state.owner)?);
+            continue;
+        }
+        let (token_id, owner) = tickets.remove(draw_index(&secret, &seed, raffle_id, draw as u64, tickets.len()));
+        let owner = ticket_holder(deps.as_ref(), &state, &raffle, &token_id, owner)?;
+        messages.push(send_prize(prize, &owner)?);
+        raffle.winners.push(RaffleWinner { prize: prize.clone(), token_id, owner });
+    }
+    raffle.status = RaffleStatus::Drawn;
+    RAFFLES.save(deps.storage, raffle_id, &raffle)?;
+    RAFFLE_TICKETS.remove(deps.storage, raffle_id);
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "draw_raffle")
+        .add_attribute("raffle_id", raffle_id.to_string())
+        .add_attribute("winners", raffle.winners.len().to_string()))
+}
+
+/// Closes a raffle without a draw and returns its prizes to the owner. Only
+/// before `draw_after`, once the draw is due the owner can not back out.
+pub fn execute_cancel_raffle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    raffle_id: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut raffle = load_open(deps.storage, raffle_id)?;
+    if env.block.time.seconds() >= raffle.draw_after {
+        return Err(ContractError::RaffleDue { raffle_id });
+    }
+    let messages = raffle
+        .prizes
+        .iter()
+        .map(|prize| send_prize(prize, &state.owner))
+        .collect::<StdResult<Vec<_>>>()?;
+    raffle.status = RaffleStatus::Cancelled;
+    RAFFLES.save(deps.storage, raffle_id, &raffle)?;
+    RAFFLE_TICKETS.remove(deps.storage, raffle_id);
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "cancel_raffle")
+        .add_attribute("raffle_id", raffle_id.to_string()))
+}
+
+pub fn query_raffle(deps: Deps, raffle_id: u64) -> StdResult<Raffle> {
+    RAFFLES.load(deps.storage, raffle_id)
+}
+
+/// Newest first, `start_after` is a raffle id.
+pub fn query_raffles(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> StdResult<RafflesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_RAFFLES_LIMIT).min(MAX_RAFFLES_LIMIT) as usize;
+    let end = start_after.map(Bound::exclusive_int);
+
+    let raffles = RAFFLES
+        .range(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|(_, raffle)| raffle))
+        .collect::<StdResult<_>>()?;
+
+    Ok(RafflesResponse { raffles })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon::tests::{previous_signature, signature, ROUND};
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, ReceiveMsg, ReceiveNftMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, Timestamp, Uint128};
+    use cw20::Cw20ReceiveMsg;
+    use cw721::Cw721ReceiveMsg;
+
+    const SECRET: [u8; 32] = [7; 32];
+
+    /// `seconds` into a raffle due at 1000, when drand publishes `ROUND`.
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(1597614570 - 1000 + seconds);
+        env
+    }
+
+    fn stake(deps: DepsMut, env: Env, owner: &str, token_id: &str) {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps, env, mock_info("nft_address1", &[]), msg).unwrap();
+    }
+
+    fn create(deps: DepsMut, sender: &str, commitment: &[u8]) -> Result<Response, ContractError> {
+        let draw_after = later(1000).block.time.seconds();
+        let msg = ExecuteMsg::CreateRaffle { commitment: HexBinary::from(commitment), draw_after };
+        execute(deps, later(100), mock_info(sender, &coins(50, "ujuno")), msg)
+    }
+
+    fn seal(deps: DepsMut, previous_signature: HexBinary, signature: HexBinary) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::SealRaffle { raffle_id: 1, previous_signature, signature };
+        execute(deps, later(1000), mock_info("anyone", &[]), msg)
+    }
+
+    fn draw(deps: DepsMut, seconds: u64, secret: Option<&[u8]>) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::DrawRaffle { raffle_id: 1, secret: secret.map(HexBinary::from) };
+        execute(deps, later(seconds), mock_info("anyone", &[]), msg)
+    }
+
+    fn nft_prize(sender: &str, token_id: &str) -> ExecuteMsg {
+        ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&ReceiveNftMsg::RafflePrize { raffle_id: 1 }).unwrap(),
+        })
+    }
+
+    #[test]
+    fn prizes_are_escrowed_by_the_owner() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let commitment = Sha256::digest(SECRET);
+
+        let err = create(deps.as_mut(), "anyone", &commitment).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = create(deps.as_mut(), "creator", &SECRET[..16]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCommitment {}));
+        let msg = ExecuteMsg::CreateRaffle { commitment: HexBinary::from(&commitment[..]), draw_after: 100 };
+        let err = execute(deps.as_mut(), later(100), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDrawTime {}));
+        create(deps.as_mut(), "creator", &commitment).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("rare_nft", &[]), nft_prize("anyone", "9")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("rare_nft", &[]), nft_prize("creator", "9")).unwrap();
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128::new(300),
+            msg: to_json_binary(&ReceiveMsg::RafflePrize { raffle_id: 1 }).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("hope_token", &[]), msg).unwrap();
+
+        let raffle = query_raffle(deps.as_ref(), 1).unwrap();
+        assert_eq!(raffle.prizes, [
+            RafflePrize::Native { denom: "ujuno".to_string(), amount: Uint128::new(50) },
+            RafflePrize::Cw721 { address: "rare_nft".to_string(), token_id: "9".to_string() },
+            RafflePrize::Cw20 { address: "hope_token".to_string(), amount: Uint128::new(300) },
+        ]);
+        assert_eq!(raffle.created_at, later(100).block.time.seconds());
+        assert_eq!(raffle.status, RaffleStatus::Open);
+        assert_eq!(raffle.beacon_round, ROUND);
+        assert_eq!(raffle.reveal_deadline, later(1000 + REVEAL_WINDOW).block.time.seconds());
+
+        let cancel = ExecuteMsg::CancelRaffle { raffle_id: 1 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        // once the draw is due the owner can not take the prizes back
+        let err = execute(deps.as_mut(), later(1000), mock_info("creator", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleDue { raffle_id: 1 }));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), cancel.clone()).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(res.messages[1].msg, send_prize(&raffle.prizes[1], "creator").unwrap());
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), cancel).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleClosed { raffle_id: 1 }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("rare_nft", &[]), nft_prize("creator", "8")).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleClosed { raffle_id: 1 }));
+    }
+
+    #[test]
+    fn draw_picks_distinct_tickets_from_the_secret_and_seal() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), mock_env(), "owner1", "1");
+        stake(deps.as_mut(), mock_env(), "owner2", "2");
+        let res = create(deps.as_mut(), "creator", &Sha256::digest(SECRET)).unwrap();
+        assert_eq!(res.attributes[2].value, "2");
+        // staked after the raffle opened, holds no ticket, while unstaking keeps it
+        stake(deps.as_mut(), later(200), "owner3", "3");
+        let msg = ExecuteMsg::UnstakeNft { token_id: "2".to_string() };
+        execute(deps.as_mut(), later(200), mock_info("owner2", &[]), msg).unwrap();
+        for token_id in ["10", "11", "12"] {
+            execute(deps.as_mut(), mock_env(), mock_info("rare_nft", &[]), nft_prize("creator", token_id)).unwrap();
+        }
+
+        // only the signature of the round fixed at creation seals it
+        let err = seal(deps.as_mut(), signature(), signature()).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBeacon { round: ROUND }));
+        let err = seal(deps.as_mut(), previous_signature(), previous_signature()).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBeacon { round: ROUND }));
+        let err = draw(deps.as_mut(), 1000, Some(&SECRET)).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleNotSealed { raffle_id: 1 }));
+        seal(deps.as_mut(), previous_signature(), signature()).unwrap();
+        let err = seal(deps.as_mut(), previous_signature(), signature()).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleClosed { raffle_id: 1 }));
+        let raffle = query_raffle(deps.as_ref(), 1).unwrap();
+        assert_eq!(raffle.status, RaffleStatus::Sealed);
+        assert_eq!(raffle.seed, Some(HexBinary::from(&Sha256::digest(signature().as_slice())[..])));
+        let prize = nft_prize("creator", "13");
+        let err = execute(deps.as_mut(), later(1001), mock_info("rare_nft", &[]), prize).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleClosed { raffle_id: 1 }));
+
+        let err = draw(deps.as_mut(), 1001, Some(&[8; 32])).unwrap_err();
+        assert!(matches!(err, ContractError::WrongSecret {}));
+        let res = draw(deps.as_mut(), 1001, Some(&SECRET)).unwrap();
+        assert_eq!(res.messages.len(), 4);
+
+        let raffle = query_raffle(deps.as_ref(), 1).unwrap();
+        assert_eq!(raffle.status, RaffleStatus::Drawn);
+        assert!(RAFFLE_TICKETS.may_load(&deps.storage, 1).unwrap().is_none());
+        let mut winners: Vec<_> = raffle.winners.iter().map(|winner| winner.token_id.as_str()).collect();
+        assert_eq!(winners.len(), 2);
+        winners.sort_unstable();
+        assert_eq!(winners, ["1", "2"]);
+        for (winner, msg) in raffle.winners.iter().zip(&res.messages) {
+            assert_eq!(msg.msg, send_prize(&winner.prize, &winner.owner).unwrap());
+        }
+        // two tickets for four prizes, the rest goes back to the owner
+        assert_eq!(res.messages[2].msg, send_prize(&raffle.prizes[2], "creator").unwrap());
+        assert_eq!(res.messages[3].msg, send_prize(&raffle.prizes[3], "creator").unwrap());
+
+        let err = draw(deps.as_mut(), 1001, Some(&SECRET)).unwrap_err();
+        assert!(matches!(err, ContractError::RaffleClosed { raffle_id: 1 }));
+    }
+
+    #[test]
+    fn a_withheld_secret_does_not_lock_the_prizes() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), mock_env(), "owner1", "1");
+        create(deps.as_mut(), "creator", &Sha256::digest(SECRET)).unwrap();
+        seal(deps.as_mut(), previous_signature(), signature()).unwrap();
+
+        let deadline = 1000 + REVEAL_WINDOW;
+        let err = draw(deps.as_mut(), deadline - 1, None).unwrap_err();
+        assert!(matches!(err, ContractError::RevealPending { raffle_id: 1, .. }));
+        let err = draw(deps.as_mut(), deadline - 1, Some(&[])).unwrap_err();
+        assert!(matches!(err, ContractError::WrongSecret {}));
+
+        // past the deadline anyone draws from the seed alone
+        let res = draw(deps.as_mut(), deadline, None).unwrap();
+        assert_eq!(res.messages[0].msg, send_prize(&RafflePrize::Native {
+            denom: "ujuno".to_string(),
+            amount: Uint128::new(50),
+        }, "owner1").unwrap());
+        assert_eq!(query_raffle(deps.as_ref(), 1).unwrap().status, RaffleStatus::Drawn);
+    }
+
+    #[test]
+    fn short_secrets_are_rejected_and_raffles_list_newest_first() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        create(deps.as_mut(), "creator", &Sha256::digest(b"short")).unwrap();
+        create(deps.as_mut(), "creator", &Sha256::digest(SECRET)).unwrap();
+
+        seal(deps.as_mut(), previous_signature(), signature()).unwrap();
+        let err = draw(deps.as_mut(), 1000, Some(b"short")).unwrap_err();
+        assert!(matches!(err, ContractError::WrongSecret {}));
+        let msg = ExecuteMsg::DrawRaffle { raffle_id: 3, secret: Some(HexBinary::from(&SECRET[..])) };
+        let err = execute(deps.as_mut(), later(1000), mock_info("anyone", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownRaffle { raffle_id: 3 }));
+
+        let ids = |res: RafflesResponse| res.raffles.iter().map(|raffle| raffle.id).collect::<Vec<_>>();
+        assert_eq!(ids(query_raffles(deps.as_ref(), None, None).unwrap()), [2, 1]);
+        assert_eq!(ids(query_raffles(deps.as_ref(), Some(2), Some(1)).unwrap()), [1]);
+    }
+}
@@ -0,0 +1,261 @@
+use cosmwasm_std::{
+    to_json_binary, CosmosMsg, DepsMut, Env, MessageInfo, QuerierWrapper, Reply, Response, StdResult, Storage,
+    SubMsg, WasmMsg,
+};
+use cw721::{Cw721ReceiveMsg, OwnerOfResponse};
+use cw_utils::parse_reply_instantiate_data;
+use cw721_base::{Extension, MintMsg};
+
+use crate::contract::{move_position, withdraw_position};
+use crate::error::ContractError;
+use crate::msg::ReceiptConfig;
+use crate::state::{State, TokenInfo, CONFIG, TOKENINFO};
+
+pub(crate) const INSTANTIATE_RECEIPTS_REPLY_ID: u64 = 1;
+
+type ReceiptExecuteMsg = cw721_base::ExecuteMsg<Extension>;
+
+/// Instantiates the receipt collection with this contract as its minter, the
+/// address is stored when the reply comes back.
+pub(crate) fn instantiate_receipts(env: &Env, owner: &str, config: ReceiptConfig) -> StdResult<SubMsg> {
+    let instantiate = WasmMsg::Instantiate {
+        admin: Some(owner.to_string()),
+        code_id: config.code_id,
+        msg: to_json_binary(&cw721_base::InstantiateMsg {
+            name: config.name,
+            symbol: config.symbol,
+            minter: env.contract.address.to_string(),
+        })?,
+        funds: vec![],
+        label: "staking receipts".to_string(),
+    };
+    Ok(SubMsg::reply_on_success(instantiate, INSTANTIATE_RECEIPTS_REPLY_ID))
+}
+
+pub(crate) fn reply_instantiate_receipts(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let address = parse_reply_instantiate_data(msg)
+        .map_err(|err| ContractError::InstantiateFailed { error: err.to_string() })?
+        .contract_address;
+
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.receipt_address = Some(address.clone());
+        Ok(state)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_receipts")
+        .add_attribute("receipt_address", address))
+}
+
+/// The receipt shares the `token_id` of the staked NFT.
+pub(crate) fn mint_receipt(receipt_address: &str, token_id: &str, owner: &str) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: receipt_address.to_string(),
+        msg: to_json_binary(&ReceiptExecuteMsg::Mint(MintMsg {
+            token_id: token_id.to_string(),
+            owner: owner.to_string(),
+            token_uri: None,
+            extension: None,
+        }))?,
+        funds: vec![],
+    }))
+}
+
+fn burn_receipt(receipt_address: &str, token_id: &str) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: receipt_address.to_string(),
+        msg: to_json_binary(&ReceiptExecuteMsg::Burn { token_id: token_id.to_string() })?,
+        funds: vec![],
+    }))
+}
+
+/// Whoever holds the receipt of `token_id`, or `owner` without receipts.
+pub(crate) fn position_holder(
+    querier: &QuerierWrapper,
+    state: &State,
+    token_id: &str,
+    owner: String,
+) -> StdResult<String> {
+    let receipt_address = match &state.receipt_address {
+        Some(address) => address,
+        None => return Ok(owner),
+    };
+    let holder: OwnerOfResponse = querier.query_wasm_smart(
+        receipt_address,
+        &cw721_base::QueryMsg::OwnerOf { token_id: token_id.to_string(), include_expired: None },
+    )?;
+    Ok(holder.owner)
+}
+
+/// Hands the position of `token` to the current holder of its receipt, so the
+/// usual owner checks apply to them. Does nothing without receipts.
+pub(crate) fn follow_receipt(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    state: &State,
+    token: &mut TokenInfo,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    if state.receipt_address.is_none() {
+        return Ok(vec![]);
+    }
+    let holder = position_holder(querier, state, &token.token_id, token.owner.clone())?;
+    move_position(storage, querier, token, &holder, &env.block)
+}
+
+/// A receipt collection instantiated from plain cw721-base does not tell us
+/// about transfers, so the buyer of a receipt (or anyone) calls this to hand
+/// the positions over before the next claim or unstake would.
+pub fn execute_sync_receipts(deps: DepsMut, env: Env, token_ids: Vec<String>) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if state.receipt_address.is_none() {
+        return Err(ContractError::NoReceipts {});
+    }
+
+    let mut response = Response::new().add_attribute("action", "sync_receipts");
+    for token_id in token_ids {
+        let mut token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+            Some(token) => token,
+            None => return Err(ContractError::NotStaked {}),
+        };
+        let previous = token.owner.clone();
+        let messages = follow_receipt(deps.storage, &deps.querier, &env, &state, &mut token)?;
+        if token.owner != previous {
+            response = response.add_messages(messages).add_attribute("moved", token_id);
+        }
+    }
+    Ok(response)
+}
+
+/// The receipt collection reports each transfer, so the position, its voting
+/// power, points and sets move with the receipt in the same transaction.
+pub fn execute_receipt_transferred(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if state.receipt_address.as_deref() != Some(info.sender.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut token = match TOKENINFO.may_load(deps.storage, &token_id)? {
+        Some(token) => token,
+        None => return Err(ContractError::NotStaked {}),
+    };
+    let messages = follow_receipt(deps.storage, &deps.querier, &env, &state, &mut token)?;
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "receipt_transferred")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", token.owner))
+}
+
+/// A receipt sent back to the contract withdraws its NFT and rewards to the
+/// sender and burns the receipt.
+pub(crate) fn execute_redeem_receipt(
+    deps: DepsMut,
+    env: Env,
+    state: State,
+    receipt_address: &str,
+    rcv_msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let mut token = match TOKENINFO.may_load(deps.storage, &rcv_msg.token_id)? {
+        Some(token) => token,
+        None => return Err(ContractError::NotStaked {}),
+    };
+    let moved = move_position(deps.storage, &deps.querier, &mut token, &rcv_msg.sender, &env.block)?;
+
+    let response = withdraw_position(deps, env, &state, token)?;
+    Ok(response.add_messages(moved).add_message(burn_receipt(receipt_address, &rcv_msg.token_id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate, reply};
+    use crate::msg::ExecuteMsg;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{from_json, Binary, SubMsgResponse, SubMsgResult};
+
+    fn receipts_msg() -> crate::msg::InstantiateMsg {
+        crate::msg::InstantiateMsg {
+            receipts: Some(ReceiptConfig {
+                code_id: 7,
+                name: "Staked Hope".to_string(),
+                symbol: "SHOPE".to_string(),
+            }),
+            ..instantiate_msg()
+        }
+    }
+
+    /// A reply carrying `MsgInstantiateContractResponse { contract_address }`.
+    fn instantiated(address: &str) -> Reply {
+        let mut data = vec![0x0a, address.len() as u8];
+        data.extend_from_slice(address.as_bytes());
+        Reply {
+            id: INSTANTIATE_RECEIPTS_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: Some(Binary::from(data)) }),
+        }
+    }
+
+    #[test]
+    fn instantiates_and_registers_the_receipt_collection() {
+        let mut deps = mock_dependencies();
+        let res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), receipts_msg()).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, INSTANTIATE_RECEIPTS_REPLY_ID);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Instantiate { admin, code_id, msg, .. }) => {
+                assert_eq!(admin.as_deref(), Some("creator"));
+                assert_eq!(*code_id, 7);
+                let msg: cw721_base::InstantiateMsg = from_json(msg).unwrap();
+                assert_eq!(msg.symbol, "SHOPE");
+                assert_eq!(msg.minter, mock_env().contract.address.to_string());
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().receipt_address, None);
+
+        let err = reply(deps.as_mut(), mock_env(), Reply { id: 9, ..instantiated("receipts") }).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownReply { id: 9 }));
+        reply(deps.as_mut(), mock_env(), instantiated("receipts")).unwrap();
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().receipt_address, Some("receipts".to_string()));
+    }
+
+    #[test]
+    fn staking_mints_and_withdrawing_needs_the_receipt() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), receipts_msg()).unwrap();
+        reply(deps.as_mut(), mock_env(), instantiated("receipts")).unwrap();
+
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "owner1".to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, mint_receipt("receipts", "1", "owner1").unwrap());
+
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::ReceiptRequired {}));
+        let msg = ExecuteMsg::TransferStake { token_id: "1".to_string(), recipient: "owner2".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::TransferReceipt {}));
+    }
+
+    #[test]
+    fn syncing_needs_receipts() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let msg = ExecuteMsg::SyncReceipts { token_ids: vec!["1".to_string()] };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::NoReceipts {}));
+    }
+}
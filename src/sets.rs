@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Deps, DepsMut, MessageInfo, Order, QuerierWrapper, Response, StdResult, Storage};
+use cw721::{Cw721QueryMsg, NftInfoResponse};
+use serde::Deserialize;
+
+use crate::error::ContractError;
+use crate::math::checked_add;
+use crate::msg::{CollectionSetsResponse, CompletedSet, MySetsResponse};
+use crate::state::{
+    CollectionSet, NftTrait, SetPieces, COLLECTION_SETS, COMPLETED_SETS, CONFIG, OWNEDTOKEN, TOKENINFO, TOKEN_TRAITS,
+};
+
+/// Distribution weight of a token outside any completed set.
+pub(crate) const BASE_WEIGHT: u64 = 100;
+/// Every set is checked on each stake and unstake of its owner.
+const MAX_COLLECTION_SETS: usize = 20;
+
+/// The part of the collection's metadata extension sets are defined on.
+#[derive(Deserialize)]
+struct TraitMetadata {
+    #[serde(default)]
+    attributes: Option<Vec<NftTrait>>,
+}
+
+/// Defines a new set. Whoever already stakes every token of a token set gets
+/// the bonus right away. Trait sets need the traits of each staked token, so
+/// owners get those from their next stake, unstake or transfer.
+pub fn execute_add_collection_set(
+    deps: DepsMut,
+    info: MessageInfo,
+    set: CollectionSet,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if set.bonus_percent == 0 {
+        return Err(ContractError::InvalidSetBonus { percent: set.bonus_percent });
+    }
+    let pieces = match &set.pieces {
+        SetPieces::Traits { values, .. } => values,
+        SetPieces::Tokens { token_ids } => token_ids,
+    };
+    let mut distinct = pieces.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if pieces.is_empty() || distinct.len() != pieces.len() {
+        return Err(ContractError::InvalidSetPieces {});
+    }
+    if COLLECTION_SETS.has(deps.storage, &set.name) {
+        return Err(ContractError::SetAlreadyDefined { name: set.name });
+    }
+    let count = COLLECTION_SETS.keys(deps.storage, None, None, Order::Ascending).count();
+    if count >= MAX_COLLECTION_SETS {
+        return Err(ContractError::TooManySets { max: MAX_COLLECTION_SETS as u64 });
+    }
+    COLLECTION_SETS.save(deps.storage, &set.name, &set)?;
+    if let SetPieces::Tokens { token_ids } = &set.pieces {
+        if let Some(token) = TOKENINFO.may_load(deps.storage, &token_ids[0])? {
+            if token.status == "Staked" {
+                refresh(deps.storage, &deps.querier, &token.owner)?;
+            }
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "add_collection_set")
+        .add_attribute("name", set.name)
+        .add_attribute("bonus_percent", set.bonus_percent.to_string()))
+}
+
+/// Removes a set and the bonus of everyone who completed it.
+pub fn execute_remove_collection_set(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !COLLECTION_SETS.has(deps.storage, &name) {
+        return Err(ContractError::UnknownSet { name });
+    }
+    COLLECTION_SETS.remove(deps.storage, &name);
+    let owners = COMPLETED_SETS
+        .prefix(&name)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for owner in owners.iter() {
+        COMPLETED_SETS.remove(deps.storage, (&name, owner));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_collection_set")
+        .add_attribute("name", name)
+        .add_attribute("completions", owners.len().to_string()))
+}
+
+/// Traits of a staked token, queried from the collection the first time. A
+/// failed query or unreadable metadata counts as no traits for now, so a
+/// broken collection never blocks staking, and is asked again next time.
+fn token_traits(storage: &mut dyn Storage, querier: &QuerierWrapper, token_id: &str) -> StdResult<Vec<NftTrait>> {
+    if let Some(traits) = TOKEN_TRAITS.may_load(storage, token_id)? {
+        return Ok(traits);
+    }
+    let state = CONFIG.load(storage)?;
+    let info: NftInfoResponse<Option<TraitMetadata>> = match querier.query_wasm_smart(
+        state.nft_address,
+        &Cw721QueryMsg::NftInfo { token_id: token_id.to_string() },
+    ) {
+        Ok(info) => info,
+        Err(_) => return Ok(vec![]),
+    };
+    let traits = info.extension.and_then(|metadata| metadata.attributes).unwrap_or_default();
+    TOKEN_TRAITS.save(storage, token_id, &traits)?;
+    Ok(traits)
+}
+
+pub(crate) fn clear_traits(storage: &mut dyn Storage, token_id: &str) {
+    TOKEN_TRAITS.remove(storage, token_id);
+}
+
+/// Records which sets `owner` completes with their "Staked" tokens. Called
+/// whenever one of their tokens enters or leaves that status or changes owner.
+pub(crate) fn refresh(storage: &mut dyn Storage, querier: &QuerierWrapper, owner: &str) -> StdResult<()> {
+    let sets = COLLECTION_SETS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, set)| set))
+        .collect::<StdResult<Vec<_>>>()?;
+    if sets.is_empty() {
+        return Ok(());
+    }
+
+    let mut staked = vec![];
+    for token_id in OWNEDTOKEN.may_load(storage, owner)?.unwrap_or_default() {
+        if TOKENINFO.load(storage, &token_id)?.status == "Staked" {
+            staked.push(token_id);
+        }
+    }
+    staked.sort_unstable();
+
+    let mut traits = BTreeMap::new();
+    if sets.iter().any(|set| matches!(set.pieces, SetPieces::Traits { .. })) {
+        for token_id in staked.iter() {
+            traits.insert(token_id.clone(), token_traits(storage, querier, token_id)?);
+        }
+    }
+
+    for set in sets {
+        let members = match set.pieces {
+            SetPieces::Tokens { token_ids } => {
+                Some(token_ids).filter(|token_ids| token_ids.iter().all(|id| staked.binary_search(id).is_ok()))
+            }
+            // the lowest token id carrying each value stands in for it
+            SetPieces::Traits { trait_type, values } => values
+                .iter()
+                .map(|value| {
+                    traits
+                        .iter()
+                        .find(|(_, traits)| {
+                            traits.iter().any(|attr| attr.trait_type == trait_type && attr.value == *value)
+                        })
+                        .map(|(token_id, _)| token_id.clone())
+                })
+                .collect::<Option<Vec<_>>>(),
+        };
+        match members {
+            Some(members) => COMPLETED_SETS.save(storage, (&set.name, owner), &members)?,
+            None => COMPLETED_SETS.remove(storage, (&set.name, owner)),
+        }
+    }
+    Ok(())
+}
+
+/// Distribution weight of every token in a completed set. A token in several
+/// sets gets the largest of their bonuses.
+pub(crate) fn set_weights(storage: &dyn Storage) -> Result<BTreeMap<String, u64>, ContractError> {
+    let mut weights = BTreeMap::new();
+    for item in COLLECTION_SETS.range(storage, None, None, Order::Ascending) {
+        let (name, set) = item?;
+        let weight = checked_add(BASE_WEIGHT, set.bonus_percent)?;
+        for item in COMPLETED_SETS.prefix(&name).range(storage, None, None, Order::Ascending) {
+            for token_id in item?.1 {
+                let entry = weights.entry(token_id).or_insert(weight);
+                *entry = (*entry).max(weight);
+            }
+        }
+    }
+    Ok(weights)
+}
+
+pub fn query_my_sets(deps: Deps, address: String) -> StdResult<MySetsResponse> {
+    let mut sets = vec![];
+    for item in COLLECTION_SETS.range(deps.storage, None, None, Order::Ascending) {
+        let (name, set) = item?;
+        if let Some(token_ids) = COMPLETED_SETS.may_load(deps.storage, (&name, &address))? {
+            sets.push(CompletedSet { name, bonus_percent: set.bonus_percent, token_ids });
+        }
+    }
+    Ok(MySetsResponse { address, sets })
+}
+
+pub fn query_collection_sets(deps: Deps) -> StdResult<CollectionSetsResponse> {
+    let sets = COLLECTION_SETS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, set)| set))
+        .collect::<StdResult<_>>()?;
+    Ok(CollectionSetsResponse { sets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate};
+    use crate::msg::ExecuteMsg;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{
+        coins, from_json, to_json_binary, ContractResult, Env, SystemError, SystemResult, Uint128, WasmQuery,
+    };
+    use cw721::Cw721ReceiveMsg;
+    use serde::Serialize;
+
+    /// On-chain metadata as cw721-metadata-onchain stores it, in part.
+    #[derive(Serialize)]
+    struct Metadata {
+        name: String,
+        attributes: Vec<NftTrait>,
+    }
+
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn stake(deps: DepsMut, owner: &str, token_id: &str) {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+    }
+
+    fn token_set(name: &str, bonus_percent: u64, token_ids: &[&str]) -> CollectionSet {
+        CollectionSet {
+            name: name.to_string(),
+            bonus_percent,
+            pieces: SetPieces::Tokens { token_ids: token_ids.iter().map(|id| id.to_string()).collect() },
+        }
+    }
+
+    fn add_set(deps: DepsMut, sender: &str, set: CollectionSet) -> Result<Response, ContractError> {
+        execute(deps, mock_env(), mock_info(sender, &[]), ExecuteMsg::AddCollectionSet { set })
+    }
+
+    fn set_names(deps: Deps, owner: &str) -> Vec<String> {
+        query_my_sets(deps, owner.to_string()).unwrap().sets.into_iter().map(|set| set.name).collect()
+    }
+
+    fn rewards(deps: Deps, token_ids: &[&str]) -> Vec<u128> {
+        token_ids.iter().map(|id| TOKENINFO.load(deps.storage, id).unwrap().reward_juno.u128()).collect()
+    }
+
+    #[test]
+    fn only_the_owner_defines_valid_sets() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let err = add_set(deps.as_mut(), "anyone", token_set("pair", 50, &["1", "2"])).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = add_set(deps.as_mut(), "creator", token_set("pair", 0, &["1", "2"])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSetBonus { percent: 0 }));
+        let err = add_set(deps.as_mut(), "creator", token_set("pair", 50, &[])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSetPieces {}));
+        let err = add_set(deps.as_mut(), "creator", token_set("pair", 50, &["1", "1"])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSetPieces {}));
+        add_set(deps.as_mut(), "creator", token_set("pair", 50, &["1", "2"])).unwrap();
+        let err = add_set(deps.as_mut(), "creator", token_set("pair", 20, &["3"])).unwrap_err();
+        assert!(matches!(err, ContractError::SetAlreadyDefined { .. }));
+        for index in 1..MAX_COLLECTION_SETS {
+            add_set(deps.as_mut(), "creator", token_set(&format!("set{}", index), 20, &["3"])).unwrap();
+        }
+        let err = add_set(deps.as_mut(), "creator", token_set("one_more", 20, &["3"])).unwrap_err();
+        assert!(matches!(err, ContractError::TooManySets { max: 20 }));
+        assert_eq!(query_collection_sets(deps.as_ref()).unwrap().sets.len(), MAX_COLLECTION_SETS);
+
+        let msg = ExecuteMsg::RemoveCollectionSet { name: "pair".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownSet { .. }));
+    }
+
+    #[test]
+    fn completed_token_sets_weigh_more_in_distributions() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        add_set(deps.as_mut(), "creator", token_set("pair", 50, &["1", "2"])).unwrap();
+        add_set(deps.as_mut(), "creator", token_set("trio", 100, &["1", "2", "3"])).unwrap();
+
+        stake(deps.as_mut(), "owner1", "1");
+        assert!(set_names(deps.as_ref(), "owner1").is_empty());
+        stake(deps.as_mut(), "owner1", "2");
+        stake(deps.as_mut(), "owner2", "3");
+        let sets = query_my_sets(deps.as_ref(), "owner1".to_string()).unwrap().sets;
+        assert_eq!(sets, [CompletedSet {
+            name: "pair".to_string(),
+            bonus_percent: 50,
+            token_ids: vec!["1".to_string(), "2".to_string()],
+        }]);
+
+        // weights 150, 150 and 100
+        let info = mock_info("reward_wallet", &coins(400, "ujuno"));
+        execute(deps.as_mut(), later(100), info, ExecuteMsg::DistributeReward {}).unwrap();
+        assert_eq!(rewards(deps.as_ref(), &["1", "2", "3"]), [150, 150, 100]);
+
+        // the larger bonus wins once owner1 holds both sets
+        let msg = ExecuteMsg::TransferStake { token_id: "3".to_string(), recipient: "owner1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner2", &[]), msg).unwrap();
+        assert_eq!(set_names(deps.as_ref(), "owner1"), ["pair", "trio"]);
+        let info = mock_info("reward_wallet", &coins(600, "ujuno"));
+        execute(deps.as_mut(), later(200), info, ExecuteMsg::DistributeReward {}).unwrap();
+        assert_eq!(rewards(deps.as_ref(), &["1", "2", "3"]), [350, 350, 200]);
+
+        let msg = ExecuteMsg::UnstakeNft { token_id: "2".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap();
+        assert!(set_names(deps.as_ref(), "owner1").is_empty());
+        assert!(set_weights(&deps.storage).unwrap().is_empty());
+
+        // a set counts as soon as it is defined for whoever already stakes it
+        add_set(deps.as_mut(), "creator", token_set("solo", 10, &["3"])).unwrap();
+        assert_eq!(set_names(deps.as_ref(), "owner1"), ["solo"]);
+        // weights 100, 100 for the token still in its cooldown, and 110
+        let info = mock_info("reward_wallet", &coins(310, "ujuno"));
+        execute(deps.as_mut(), later(300), info, ExecuteMsg::DistributeReward {}).unwrap();
+        assert_eq!(rewards(deps.as_ref(), &["1", "2", "3"]), [450, 450, 310]);
+        let dust = crate::contract::query_state_info(deps.as_ref()).unwrap().reward_dust;
+        assert_eq!(dust, Uint128::zero());
+    }
+
+    #[test]
+    fn unreadable_metadata_counts_as_no_traits() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|_| SystemResult::Err(SystemError::Unknown {}));
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let set = CollectionSet {
+            name: "eyes".to_string(),
+            bonus_percent: 25,
+            pieces: SetPieces::Traits { trait_type: "eyes".to_string(), values: vec!["laser".to_string()] },
+        };
+        add_set(deps.as_mut(), "creator", set).unwrap();
+
+        stake(deps.as_mut(), "owner1", "1");
+        assert!(set_names(deps.as_ref(), "owner1").is_empty());
+        // asked again once the collection answers
+        assert!(!TOKEN_TRAITS.has(&deps.storage, "1"));
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap();
+
+        let msg = br#"{"add_collection_set":{"set":{"name":"eyes","bonus_percent":25,
+            "pieces":{"tokens":{"token_ids":["1"],"extra":true}}}}}"#;
+        assert!(from_json::<ExecuteMsg>(msg).is_err());
+    }
+
+    #[test]
+    fn trait_sets_read_the_collection_metadata() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "nft_address1" => {
+                let token_id = match from_json(msg).unwrap() {
+                    Cw721QueryMsg::NftInfo { token_id } => token_id,
+                    msg => panic!("unexpected query {:?}", msg),
+                };
+                let faction = ["red", "green", "blue", "red"][token_id.parse::<usize>().unwrap()];
+                let attribute = |trait_type: &str, value: &str| NftTrait {
+                    trait_type: trait_type.to_string(),
+                    value: value.to_string(),
+                };
+                let info = NftInfoResponse {
+                    token_uri: None,
+                    extension: Metadata {
+                        name: token_id,
+                        attributes: vec![attribute("eyes", "laser"), attribute("faction", faction)],
+                    },
+                };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let factions = CollectionSet {
+            name: "factions".to_string(),
+            bonus_percent: 25,
+            pieces: SetPieces::Traits {
+                trait_type: "faction".to_string(),
+                values: vec!["red".to_string(), "green".to_string(), "blue".to_string()],
+            },
+        };
+        add_set(deps.as_mut(), "creator", factions).unwrap();
+
+        stake(deps.as_mut(), "owner1", "3");
+        stake(deps.as_mut(), "owner1", "1");
+        assert!(set_names(deps.as_ref(), "owner1").is_empty());
+        stake(deps.as_mut(), "owner1", "0");
+        stake(deps.as_mut(), "owner1", "2");
+        let sets = query_my_sets(deps.as_ref(), "owner1".to_string()).unwrap().sets;
+        assert_eq!(sets[0].token_ids, ["0", "1", "2"]);
+        assert_eq!(TOKEN_TRAITS.load(&deps.storage, "3").unwrap()[1].value, "red");
+
+        // the second red token takes over
+        let msg = ExecuteMsg::UnstakeNft { token_id: "0".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap();
+        let sets = query_my_sets(deps.as_ref(), "owner1".to_string()).unwrap().sets;
+        assert_eq!(sets[0].token_ids, ["3", "1", "2"]);
+
+        let msg = ExecuteMsg::WithdrawNft { token_id: "0".to_string() };
+        execute(deps.as_mut(), later(instantiate_msg().staking_period), mock_info("owner1", &[]), msg).unwrap();
+        assert!(!TOKEN_TRAITS.has(&deps.storage, "0"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Who had enough NFTs staked for long enough at a given time, for airdrops.
+//!
+//! Answers come from `STAKE_HISTORY`, so tokens withdrawn since `as_of` still
+//! count and a transferred position counts for each owner while they held it.
+//! [`staker_snapshot`] works on plain (owner, interval) pairs and never
+//! touches storage.
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Deps, Order, StdResult, Storage};
+use cw_storage_plus::Bound;
+
+use crate::msg::{StakerSnapshotEntry, StakerSnapshotResponse};
+use crate::state::{StakeInterval, STAKE_HISTORY};
+
+const DEFAULT_SNAPSHOT_LIMIT: u32 = 30;
+const MAX_SNAPSHOT_LIMIT: u32 = 100;
+
+/// Opens an interval for `token_id` staked with `owner` from `now`.
+pub(crate) fn start_interval(storage: &mut dyn Storage, owner: &str, token_id: &str, now: u64) -> StdResult<()> {
+    let mut history = STAKE_HISTORY.may_load(storage, owner)?.unwrap_or_default();
+    history.push(StakeInterval { token_id: token_id.to_string(), start: now, end: None });
+    STAKE_HISTORY.save(storage, owner, &history)
+}
+
+/// Closes the open interval of `token_id` with `owner` at `now`.
+pub(crate) fn end_interval(storage: &mut dyn Storage, owner: &str, token_id: &str, now: u64) -> StdResult<()> {
+    let mut history = STAKE_HISTORY.may_load(storage, owner)?.unwrap_or_default();
+    match history.iter_mut().find(|interval| interval.token_id == token_id && interval.end.is_none()) {
+        Some(open) => open.end = Some(now),
+        None => return Ok(()),
+    }
+    STAKE_HISTORY.save(storage, owner, &history)
+}
+
+/// Seconds `interval` had lasted at `as_of`, `None` if the token was not
+/// staked with its owner then.
+pub fn staked_duration(interval: &StakeInterval, as_of: u64) -> Option<u64> {
+    if interval.start > as_of || interval.end.is_some_and(|end| end <= as_of) {
+        return None;
+    }
+    Some(as_of - interval.start)
+}
+
+/// Owners with at least `min_tokens` tokens (and at least one) that had each
+/// been staked for `min_duration` seconds at `as_of`, sorted by owner.
+pub fn staker_snapshot<'a>(
+    history: impl IntoIterator<Item = (&'a str, &'a StakeInterval)>,
+    as_of: u64,
+    min_tokens: u64,
+    min_duration: u64,
+) -> Vec<StakerSnapshotEntry> {
+    let mut owners: BTreeMap<&str, StakerSnapshotEntry> = BTreeMap::new();
+    for (owner, interval) in history {
+        let duration = match staked_duration(interval, as_of) {
+            Some(duration) if duration >= min_duration => duration,
+            _ => continue,
+        };
+        let entry = owners.entry(owner).or_insert_with(|| StakerSnapshotEntry {
+            owner: owner.to_string(),
+            token_count: 0,
+            total_duration: 0,
+            longest_duration: 0,
+        });
+        entry.token_count += 1;
+        entry.total_duration = entry.total_duration.saturating_add(duration);
+        entry.longest_duration = entry.longest_duration.max(duration);
+    }
+    owners
+        .into_values()
+        .filter(|entry| entry.token_count >= min_tokens)
+        .collect()
+}
+
+/// Pages through owners rather than results, a page can come back with few
+/// or no stakers while `next_start_after` is still set.
+pub fn query_staker_snapshot(
+    deps: Deps,
+    as_of: u64,
+    min_tokens: u64,
+    min_duration: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<StakerSnapshotResponse> {
+    let limit = limit.unwrap_or(DEFAULT_SNAPSHOT_LIMIT).min(MAX_SNAPSHOT_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let history = STAKE_HISTORY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = if history.len() == limit {
+        history.last().map(|(owner, _)| owner.clone())
+    } else {
+        None
+    };
+
+    let pairs = history
+        .iter()
+        .flat_map(|(owner, intervals)| intervals.iter().map(move |interval| (owner.as_str(), interval)));
+    Ok(StakerSnapshotResponse { stakers: staker_snapshot(pairs, as_of, min_tokens, min_duration), next_start_after })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate};
+    use crate::msg::ExecuteMsg;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{to_json_binary, DepsMut, Env};
+    use cw721::Cw721ReceiveMsg;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn at(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn stake(deps: DepsMut, env: Env, owner: &str, token_id: &str) {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps, env, mock_info("nft_address1", &[]), msg).unwrap();
+    }
+
+    fn owners(stakers: &[StakerSnapshotEntry]) -> Vec<(&str, u64)> {
+        stakers.iter().map(|entry| (entry.owner.as_str(), entry.token_count)).collect()
+    }
+
+    #[test]
+    fn snapshot_counts_tokens_staked_long_enough() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), at(0), "alice", "1");
+        stake(deps.as_mut(), at(0), "alice", "2");
+        stake(deps.as_mut(), at(5 * DAY), "alice", "3");
+        stake(deps.as_mut(), at(0), "bob", "4");
+        stake(deps.as_mut(), at(0), "carol", "5");
+        stake(deps.as_mut(), at(0), "carol", "6");
+        // unstaked before the snapshot time
+        let msg = ExecuteMsg::UnstakeNft { token_id: "6".to_string() };
+        execute(deps.as_mut(), at(9 * DAY), mock_info("carol", &[]), msg).unwrap();
+
+        let as_of = at(10 * DAY).block.time.seconds();
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 1, 7 * DAY, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 2), ("bob", 1), ("carol", 1)]);
+        assert_eq!(res.stakers[0].total_duration, 20 * DAY);
+        assert_eq!(res.stakers[0].longest_duration, 10 * DAY);
+        assert_eq!(res.next_start_after, None);
+
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 2, 7 * DAY, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 2)]);
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 2, 0, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 3)]);
+        // before token 6 was unstaked and token 3 staked
+        let as_of = at(8 * DAY).block.time.seconds();
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 2, 7 * DAY, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 2), ("carol", 2)]);
+
+        // pages go by owner
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 2, 7 * DAY, None, Some(2)).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 2)]);
+        assert_eq!(res.next_start_after.as_deref(), Some("bob"));
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 2, 7 * DAY, res.next_start_after, Some(2)).unwrap();
+        assert_eq!(owners(&res.stakers), [("carol", 2)]);
+        assert_eq!(res.next_start_after, None);
+
+        // the library function agrees on an export of the history
+        let export = STAKE_HISTORY
+            .range(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        let pairs = export.iter().flat_map(|(owner, intervals)| intervals.iter().map(move |i| (owner.as_str(), i)));
+        let query = query_staker_snapshot(deps.as_ref(), as_of, 1, DAY, None, None).unwrap();
+        assert_eq!(staker_snapshot(pairs, as_of, 1, DAY), query.stakers);
+    }
+
+    #[test]
+    fn snapshot_looks_back_past_withdrawals_and_transfers() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), at(0), "alice", "1");
+        stake(deps.as_mut(), at(0), "alice", "2");
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), at(10 * DAY), mock_info("alice", &[]), msg).unwrap();
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), at(11 * DAY), mock_info("alice", &[]), msg).unwrap();
+        let msg = ExecuteMsg::TransferStake { token_id: "2".to_string(), recipient: "bob".to_string() };
+        execute(deps.as_mut(), at(12 * DAY), mock_info("alice", &[]), msg).unwrap();
+
+        // the withdrawn token still counts before it left
+        let as_of = at(9 * DAY).block.time.seconds();
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 1, 7 * DAY, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 2)]);
+
+        // bob's stake starts with the transfer
+        let as_of = at(15 * DAY).block.time.seconds();
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 1, 0, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("bob", 1)]);
+        assert_eq!(res.stakers[0].longest_duration, 3 * DAY);
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 1, 7 * DAY, None, None).unwrap();
+        assert!(res.stakers.is_empty());
+
+        // restaking opens a new interval
+        stake(deps.as_mut(), at(20 * DAY), "alice", "1");
+        let as_of = at(21 * DAY).block.time.seconds();
+        let res = query_staker_snapshot(deps.as_ref(), as_of, 1, 0, None, None).unwrap();
+        assert_eq!(owners(&res.stakers), [("alice", 1), ("bob", 1)]);
+        assert_eq!(res.stakers[0].longest_duration, DAY);
+        let history = STAKE_HISTORY.load(&deps.storage, "alice").unwrap();
+        assert_eq!(history.iter().filter(|interval| interval.token_id == "1").count(), 2);
+    }
+}
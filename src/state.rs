@@ -1,6 +1,6 @@
-use cosmwasm_std::{ Uint128};
+use cosmwasm_std::{HexBinary, Uint128};
 
-use cw_storage_plus::{Item, Map};
+use cw_storage_plus::{Item, Map, SnapshotItem, SnapshotMap, Strategy};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -8,6 +8,21 @@ use serde::{Deserialize, Serialize};
 pub const CONFIG: Item<State> = Item::new("config_state");
 pub const TOKENINFO : Map<&str,TokenInfo> = Map::new("config_nfts");
 pub const OWNEDTOKEN : Map<&str, Vec<String>>= Map::new("config_owned");
+/// Number of "Staked" tokens per owner, with the history behind
+/// `VotingPowerAtHeight`. Like `OWNEDTOKEN` it follows `TokenInfo.owner`.
+pub const STAKED_POWER: SnapshotMap<&str, Uint128> = SnapshotMap::new(
+    "staked_power",
+    "staked_power__checkpoints",
+    "staked_power__changelog",
+    Strategy::EveryBlock,
+);
+/// Sum of `STAKED_POWER`, follows `State.total_staked` with history.
+pub const TOTAL_POWER: SnapshotItem<Uint128> = SnapshotItem::new(
+    "total_power",
+    "total_power__checkpoints",
+    "total_power__changelog",
+    Strategy::EveryBlock,
+);
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
@@ -17,10 +32,39 @@ pub struct State {
     pub reward_wallet : String,
     pub total_staked : Uint128,
     pub nft_address : String,
-    pub token_address : String,
+    /// Optional CW20 reward token.
+    pub token_address : Option<String>,
     pub can_stake : bool,
     pub last_distribute:u64,
-    pub distribute_period:u64
+    pub distribute_period:u64,
+    /// Remainder of past distributions that is added to the next one.
+    #[serde(default)]
+    pub reward_dust:Uint128,
+    /// Seconds a token must be staked before it shares in distributions.
+    #[serde(default)]
+    pub min_stake_age:u64,
+    /// Emission weight of the NFT pool against the weights of the LP pools.
+    #[serde(default = "default_nft_weight")]
+    pub nft_weight:u64,
+    /// Whether `TransferStake` also accepts tokens that are unstaking.
+    #[serde(default)]
+    pub transfer_unstaking:bool,
+    /// Loyalty points earned per "Staked" token per second.
+    #[serde(default)]
+    pub points_rate:u64,
+    /// Seconds after a distribution before the NFT rewards it credited can be
+    /// swept if still unclaimed, 0 keeps them forever. Applies to past credits.
+    #[serde(default)]
+    pub reward_expiry:u64,
+    /// cw721-base collection of liquid staking receipts, set once the contract
+    /// has instantiated it. A position belongs to whoever holds its receipt,
+    /// `TokenInfo.owner` catches up when the holder next unstakes or claims.
+    pub receipt_address:Option<String>
+}
+
+/// Configs stored before pools existed gave every distribution to the NFTs.
+fn default_nft_weight() -> u64 {
+    crate::contract::DEFAULT_NFT_WEIGHT
 }
 
 
@@ -33,4 +77,278 @@ pub struct TokenInfo {
     pub unstake_time:u64,
     pub stake_time:u64,
     pub reward_juno:Uint128
-}
\ No newline at end of file
+}
+pub const POOLS: Map<&str, FungiblePool> = Map::new("pools");
+/// Positions keyed by (pool id, owner).
+pub const POSITIONS: Map<(&str, &str), StakePosition> = Map::new("positions");
+/// Contracts sent a `StakeChangedHook` on every stake, unstake and withdraw.
+pub const HOOKS: Item<Vec<String>> = Item::new("hooks");
+pub const POINTS_INDEX: Item<PointsIndex> = Item::new("points_index");
+/// Loyalty points per owner, kept after their tokens are withdrawn.
+pub const POINTS: Map<&str, PointsAccount> = Map::new("points");
+/// Contracts allowed to spend points besides the contract owner.
+pub const POINT_SPENDERS: Item<Vec<String>> = Item::new("point_spenders");
+pub const POINT_SPEND_COUNT: Item<u64> = Item::new("point_spend_count");
+/// Spends keyed by (owner, spend id).
+pub const POINT_SPENDS: Map<(&str, u64), PointSpend> = Map::new("point_spends");
+/// The unclaimed distributions behind each `TokenInfo.reward_juno`, by token id.
+pub const REWARD_CREDITS: Map<&str, Vec<RewardCredit>> = Map::new("reward_credits");
+pub const RAFFLE_COUNT: Item<u64> = Item::new("raffle_count");
+pub const RAFFLES: Map<u64, Raffle> = Map::new("raffles");
+/// The (token id, owner) of every ticket, fixed when the raffle opens.
+pub const RAFFLE_TICKETS: Map<u64, Vec<(String, String)>> = Map::new("raffle_tickets");
+pub const COLLECTION_SETS: Map<&str, CollectionSet> = Map::new("collection_sets");
+/// The staked tokens completing a set, keyed by (set name, owner).
+pub const COMPLETED_SETS: Map<(&str, &str), Vec<String>> = Map::new("completed_sets");
+/// Traits read from the collection, cached until the token is withdrawn.
+pub const TOKEN_TRAITS: Map<&str, Vec<NftTrait>> = Map::new("token_traits");
+pub const VESTING_POLICY: Item<VestingPolicy> = Item::new("vesting_policy");
+/// Claimed rewards that are still vesting, per owner.
+pub const VESTING: Map<&str, Vec<VestingTranche>> = Map::new("vesting");
+/// Claimed rewards not withdrawn yet, summed over every owner's tranches.
+pub const VESTING_LOCKED: Item<Uint128> = Item::new("vesting_locked");
+pub const PENALTY_POLICY: Item<PenaltyPolicy> = Item::new("penalty_policy");
+/// Every stretch a token spent "Staked" with an owner, kept after it leaves
+/// so snapshots can look back.
+pub const STAKE_HISTORY: Map<&str, Vec<StakeInterval>> = Map::new("stake_history");
+pub const DISTRIBUTION_COUNT: Item<u64> = Item::new("distribution_count");
+pub const DISTRIBUTIONS: Map<u64, DistributionEpoch> = Map::new("distributions");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StakeInterval {
+    pub token_id: String,
+    pub start: u64,
+    /// `None` while the token is still staked with the owner.
+    pub end: Option<u64>,
+}
+
+/// One `DistributeReward` call, kept so payouts can be audited later.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DistributionEpoch {
+    pub id: u64,
+    pub time: u64,
+    pub amount: Uint128,
+    pub denom: String,
+    pub eligible_count: u64,
+    /// Total credited to LP pool positions in this epoch.
+    pub pool_rewards: Uint128,
+    /// Dust carried in from the previous epoch and shared out with `amount`.
+    pub carried_dust: Uint128,
+    /// Amount credited to each eligible NFT outside a completed set.
+    pub per_token: Uint128,
+    /// Remainder that did not divide evenly and is carried to the next epoch.
+    pub dust: Uint128,
+}
+
+/// Points one token has earned since points were introduced, as of `last_update`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PointsIndex {
+    pub per_token: Uint128,
+    pub last_update: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct PointsAccount {
+    /// Points settled so far, the ones earned since `per_token` are added
+    /// when the account is next touched.
+    pub balance: Uint128,
+    /// `PointsIndex.per_token` when the account was last settled.
+    pub per_token: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PointSpend {
+    pub id: u64,
+    pub spender: String,
+    pub amount: Uint128,
+    pub time: u64,
+    pub memo: Option<String>,
+}
+
+/// Forfeit applied to the unclaimed reward of tokens that claim, transfer or
+/// withdraw too early.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PenaltyPolicy {
+    /// Seconds after `stake_time` before a token is paid out without penalty.
+    pub min_stake_duration: u64,
+    /// Percent of the unclaimed `reward_juno` that is forfeited, 0 to 100.
+    pub percent: u64,
+    pub destination: PenaltyDestination,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardCredit {
+    /// The `DistributionEpoch` that credited `amount`.
+    pub epoch: u64,
+    pub time: u64,
+    pub amount: Uint128,
+}
+
+/// Staking every piece of a set adds `bonus_percent` to the distribution
+/// weight of each token in it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionSet {
+    pub name: String,
+    pub bonus_percent: u64,
+    pub pieces: SetPieces,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SetPieces {
+    /// One token for each of `values` of the `trait_type` attribute.
+    Traits { trait_type: String, values: Vec<String> },
+    /// Exactly these tokens.
+    Tokens { token_ids: Vec<String> },
+}
+
+/// An attribute in the on-chain metadata of the staked collection.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NftTrait {
+    pub trait_type: String,
+    pub value: String,
+}
+
+/// A draw among the staked NFTs, one ticket per token.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Raffle {
+    pub id: u64,
+    /// sha256 of the secret revealed by `DrawRaffle`, mixed with `seed` into the draw.
+    pub commitment: HexBinary,
+    /// Tokens staked after this time hold no ticket.
+    pub created_at: u64,
+    pub draw_after: u64,
+    /// The first drand round published after `draw_after`, whose randomness
+    /// nobody knows when the raffle opens.
+    pub beacon_round: u64,
+    /// Until then the draw needs the secret. After it anyone can draw with the
+    /// seed alone, so withholding the secret can not lock the prizes.
+    pub reveal_deadline: u64,
+    /// Randomness of `beacon_round` recorded by `SealRaffle`.
+    pub seed: Option<HexBinary>,
+    /// Escrowed by the contract until the draw, drawn in this order.
+    pub prizes: Vec<RafflePrize>,
+    pub status: RaffleStatus,
+    pub winners: Vec<RaffleWinner>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RafflePrize {
+    Native { denom: String, amount: Uint128 },
+    Cw20 { address: String, amount: Uint128 },
+    Cw721 { address: String, token_id: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RaffleStatus {
+    Open,
+    /// Its beacon round is recorded, waiting for the secret.
+    Sealed,
+    Drawn,
+    /// Closed without a draw, the prizes went back to the owner.
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RaffleWinner {
+    pub prize: RafflePrize,
+    /// The ticket that was drawn.
+    pub token_id: String,
+    pub owner: String,
+}
+
+/// Claimed rewards vest linearly instead of being paid out at once.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VestingPolicy {
+    /// Seconds over which each claim is released.
+    pub duration: u64,
+    /// Percent of an instant claim that is held back, 0 to 100.
+    pub instant_haircut: u64,
+    /// Where the haircut goes.
+    pub destination: PenaltyDestination,
+}
+
+/// One claim, released linearly from `start` to `end`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingTranche {
+    pub amount: Uint128,
+    pub start: u64,
+    pub end: u64,
+    pub withdrawn: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum PenaltyDestination {
+    Treasury { address: String },
+    /// Added to the carried dust and shared out by the next distribution.
+    Redistribute {},
+}
+
+/// A fungible asset that can be bonded for a share of the distributions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FungiblePool {
+    /// The CW20 contract address or the native denom of `asset`.
+    pub id: String,
+    pub asset: PoolAsset,
+    pub weight: u64,
+    pub total_bonded: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum PoolAsset {
+    /// Bonded by sending the tokens with the CW20 `Send` hook.
+    Cw20 { address: String },
+    /// Bonded by attaching funds to `BondNative`.
+    Native { denom: String },
+}
+
+impl PoolAsset {
+    pub fn id(&self) -> &str {
+        match self {
+            PoolAsset::Cw20 { address } => address,
+            PoolAsset::Native { denom } => denom,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StakePosition {
+    pub owner: String,
+    pub pool: String,
+    /// Bonded amount, including deposits that are still warming up.
+    pub bonded: Uint128,
+    /// Recent deposits that only start earning after `min_stake_age`.
+    pub warming: Vec<LockedAmount>,
+    /// Amounts in their `staking_period` cooldown.
+    pub unbonding: Vec<LockedAmount>,
+    pub reward_juno: Uint128,
+}
+
+impl StakePosition {
+    pub fn new(pool: &str, owner: &str) -> Self {
+        StakePosition {
+            owner: owner.to_string(),
+            pool: pool.to_string(),
+            bonded: Uint128::zero(),
+            warming: vec![],
+            unbonding: vec![],
+            reward_juno: Uint128::zero(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockedAmount {
+    pub amount: Uint128,
+    pub until: u64,
+    /// Set on unbonding cut from a deposit that was still warming up, it earns
+    /// nothing before this time.
+    pub warm_until: Option<u64>,
+}
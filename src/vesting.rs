@@ -0,0 +1,404 @@
+use cosmwasm_std::{
+    CosmosMsg, Deps, DepsMut, Env, MessageInfo, OverflowError, Response, StdResult, Storage, Uint128
+};
+
+use crate::contract::{reward_payout, validate_address, validate_period};
+use crate::error::ContractError;
+use crate::math::{checked_add, checked_multiply_ratio};
+use crate::msg::VestingInfoResponse;
+use crate::state::{
+    PenaltyDestination, State, VestingPolicy, VestingTranche, CONFIG, VESTING, VESTING_LOCKED, VESTING_POLICY
+};
+
+/// Most tranches kept per owner. Beyond it a claim is merged into the newest
+/// tranche, so the vesting list of a frequent claimer stays bounded.
+const MAX_TRANCHES: usize = 10;
+
+/// Replaces the vesting policy of claimed rewards, `None` pays claims out in
+/// full again. Tranches that are already vesting keep their schedule.
+pub fn execute_set_vesting_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    policy: Option<VestingPolicy>,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match policy {
+        Some(policy) => {
+            validate_period("vesting_duration", policy.duration, 1)?;
+            if policy.instant_haircut > 100 {
+                return Err(ContractError::InvalidHaircutPercent { percent: policy.instant_haircut });
+            }
+            if let PenaltyDestination::Treasury { address } = &policy.destination {
+                validate_address(deps.api, "treasury", address)?;
+            }
+            VESTING_POLICY.save(deps.storage, &policy)?;
+        }
+        None => VESTING_POLICY.remove(deps.storage),
+    }
+    Ok(Response::default())
+}
+
+/// Part of `tranche` released at `now`, withdrawn or not.
+fn released(tranche: &VestingTranche, now: u64) -> Result<Uint128, OverflowError> {
+    if now >= tranche.end {
+        return Ok(tranche.amount);
+    }
+    if now <= tranche.start {
+        return Ok(Uint128::zero());
+    }
+    checked_multiply_ratio(tranche.amount, now - tranche.start, tranche.end - tranche.start)
+}
+
+/// Folds the tranches that finished vesting by `now` into one at the front,
+/// dropping what was withdrawn from them.
+fn collapse_vested(tranches: Vec<VestingTranche>, now: u64) -> Result<Vec<VestingTranche>, OverflowError> {
+    let mut vested = Uint128::zero();
+    let mut vesting = vec![];
+    for tranche in tranches {
+        if tranche.end <= now {
+            vested = vested.checked_add(tranche.amount.checked_sub(tranche.withdrawn)?)?;
+        } else {
+            vesting.push(tranche);
+        }
+    }
+    if !vested.is_zero() {
+        vesting.insert(0, VestingTranche { amount: vested, start: now, end: now, withdrawn: Uint128::zero() });
+    }
+    Ok(vesting)
+}
+
+/// Starts a tranche of `amount` vesting until `end`. When the newest tranche
+/// ends at the same time, or the owner already has `MAX_TRANCHES`, the claim
+/// joins the newest one instead: its released part moves to the vested tranche
+/// and the rest vests with the claim, never sooner than it would have.
+fn add_tranche(tranches: Vec<VestingTranche>, amount: Uint128, now: u64, end: u64) -> Result<Vec<VestingTranche>, OverflowError> {
+    let mut tranches = collapse_vested(tranches, now)?;
+    let count = tranches.len();
+    let (amount, end) = match tranches.pop() {
+        Some(last) if last.end > now && (last.end == end || count >= MAX_TRANCHES) => {
+            let released = released(&last, now)?;
+            let carried = released.checked_sub(last.withdrawn)?;
+            match tranches.first_mut().filter(|first| first.end <= now) {
+                Some(vested) => vested.amount = vested.amount.checked_add(carried)?,
+                None if !carried.is_zero() => tranches.insert(0, VestingTranche {
+                    amount: carried,
+                    start: now,
+                    end: now,
+                    withdrawn: Uint128::zero(),
+                }),
+                None => {}
+            }
+            (amount.checked_add(last.amount.checked_sub(released)?)?, end.max(last.end))
+        }
+        Some(last) => {
+            tranches.push(last);
+            (amount, end)
+        }
+        None => (amount, end),
+    };
+    tranches.push(VestingTranche { amount, start: now, end, withdrawn: Uint128::zero() });
+    Ok(tranches)
+}
+
+/// What could be withdrawn from `tranches` at `now` and what is still vesting.
+fn vesting_totals(tranches: &[VestingTranche], now: u64) -> Result<(Uint128, Uint128), OverflowError> {
+    let mut withdrawable = Uint128::zero();
+    let mut locked = Uint128::zero();
+    for tranche in tranches {
+        let released = released(tranche, now)?;
+        withdrawable = withdrawable.checked_add(released.checked_sub(tranche.withdrawn)?)?;
+        locked = locked.checked_add(tranche.amount.checked_sub(released)?)?;
+    }
+    Ok((withdrawable, locked))
+}
+
+/// Hands `amount` of claimed reward to `owner` under the vesting policy: it
+/// starts a new tranche, or is paid now less the haircut when `instant`.
+/// Without a policy it is paid out in full.
+pub(crate) fn settle_claim(
+    storage: &mut dyn Storage,
+    state: &State,
+    owner: &str,
+    amount: Uint128,
+    instant: bool,
+    now: u64,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let policy = match VESTING_POLICY.may_load(storage)? {
+        Some(policy) => policy,
+        None => return Ok(vec![reward_payout(state, owner, amount)]),
+    };
+
+    if !instant {
+        let tranches = VESTING.may_load(storage, owner)?.unwrap_or_default();
+        let tranches = add_tranche(tranches, amount, now, checked_add(now, policy.duration)?)?;
+        VESTING.save(storage, owner, &tranches)?;
+        let locked = VESTING_LOCKED.may_load(storage)?.unwrap_or_default();
+        VESTING_LOCKED.save(storage, &locked.checked_add(amount)?)?;
+        return Ok(vec![]);
+    }
+
+    let haircut = checked_multiply_ratio(amount, policy.instant_haircut, 100u64)?;
+    let mut messages = vec![];
+    if amount > haircut {
+        messages.push(reward_payout(state, owner, amount.checked_sub(haircut)?));
+    }
+    if !haircut.is_zero() {
+        match policy.destination {
+            PenaltyDestination::Treasury { address } => messages.push(reward_payout(state, &address, haircut)),
+            // back into the pool with the next distribution
+            PenaltyDestination::Redistribute {} => {
+                CONFIG.update(storage, |mut state| -> Result<_, ContractError> {
+                    state.reward_dust = state.reward_dust.checked_add(haircut)?;
+                    Ok(state)
+                })?;
+            }
+        }
+    }
+    Ok(messages)
+}
+
+pub fn execute_withdraw_vested(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let owner = info.sender.to_string();
+    let now = env.block.time.seconds();
+
+    let mut tranches = VESTING.may_load(deps.storage, &owner)?.unwrap_or_default();
+    let mut amount = Uint128::zero();
+    for tranche in tranches.iter_mut() {
+        let released = released(tranche, now)?;
+        amount = amount.checked_add(released.checked_sub(tranche.withdrawn)?)?;
+        tranche.withdrawn = released;
+    }
+    if amount.is_zero() {
+        return Err(ContractError::TimeRemaining {});
+    }
+
+    tranches.retain(|tranche| tranche.withdrawn < tranche.amount);
+    if tranches.is_empty() {
+        VESTING.remove(deps.storage, &owner);
+    } else {
+        VESTING.save(deps.storage, &owner, &tranches)?;
+    }
+    let locked = VESTING_LOCKED.may_load(deps.storage)?.unwrap_or_default();
+    VESTING_LOCKED.save(deps.storage, &locked.checked_sub(amount)?)?;
+
+    Ok(Response::new()
+        .add_message(reward_payout(&state, &owner, amount))
+        .add_attribute("action", "withdraw_vested")
+        .add_attribute("amount", amount))
+}
+
+pub fn query_vesting_info(deps: Deps, env: Env, address: String) -> StdResult<VestingInfoResponse> {
+    let tranches = VESTING.may_load(deps.storage, &address)?.unwrap_or_default();
+    let (withdrawable, locked) = vesting_totals(&tranches, env.block.time.seconds())?;
+    Ok(VestingInfoResponse {
+        address,
+        policy: VESTING_POLICY.may_load(deps.storage)?,
+        withdrawable,
+        locked,
+        tranches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate, query_state_info};
+    use crate::msg::ExecuteMsg;
+    use crate::state::PoolAsset;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coins, to_json_binary, BankMsg, OwnedDeps};
+    use cw721::Cw721ReceiveMsg;
+
+    fn later(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn send(to_address: &str, amount: u128) -> CosmosMsg {
+        CosmosMsg::Bank(BankMsg::Send { to_address: to_address.to_string(), amount: coins(amount, "ujuno") })
+    }
+
+    /// "owner1" with token "1" holding 1000 of unclaimed reward at `later(100)`.
+    fn setup(policy: VestingPolicy) -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "owner1".to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nft_address1", &[]), msg).unwrap();
+        let info = mock_info("reward_wallet", &coins(1000, "ujuno"));
+        execute(deps.as_mut(), later(100), info, ExecuteMsg::DistributeReward {}).unwrap();
+
+        let msg = ExecuteMsg::SetVestingPolicy { policy: Some(policy) };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        deps
+    }
+
+    fn claim(instant: bool) -> ExecuteMsg {
+        ExecuteMsg::GetReward { token_ids: vec!["1".to_string()], instant: Some(instant) }
+    }
+
+    #[test]
+    fn set_vesting_policy_validates() {
+        let policy = VestingPolicy { duration: 1000, instant_haircut: 20, destination: PenaltyDestination::Redistribute {} };
+        let mut deps = setup(policy.clone());
+
+        let msg = ExecuteMsg::SetVestingPolicy { policy: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let msg = ExecuteMsg::SetVestingPolicy { policy: Some(VestingPolicy { instant_haircut: 101, ..policy.clone() }) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidHaircutPercent { percent: 101 }));
+        let msg = ExecuteMsg::SetVestingPolicy { policy: Some(VestingPolicy { duration: 0, ..policy }) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPeriod { .. }));
+        let msg = br#"{"set_vesting_policy":{"policy":{"duration":1000,"instant_haircut":20,
+            "destination":{"redistribute":{}},"cliff":100}}}"#;
+        assert!(cosmwasm_std::from_json::<ExecuteMsg>(msg).is_err());
+    }
+
+    #[test]
+    fn claims_vest_linearly() {
+        let policy = VestingPolicy { duration: 1000, instant_haircut: 20, destination: PenaltyDestination::Redistribute {} };
+        let mut deps = setup(policy);
+
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), claim(false)).unwrap();
+        assert!(res.messages.is_empty());
+        let info = query_vesting_info(deps.as_ref(), later(350), "owner1".to_string()).unwrap();
+        assert_eq!((info.withdrawable, info.locked), (Uint128::new(250), Uint128::new(750)));
+
+        let res = execute(deps.as_mut(), later(350), mock_info("owner1", &[]), ExecuteMsg::WithdrawVested {}).unwrap();
+        assert_eq!(res.messages[0].msg, send("owner1", 250));
+        let err = execute(deps.as_mut(), later(350), mock_info("owner1", &[]), ExecuteMsg::WithdrawVested {}).unwrap_err();
+        assert!(matches!(err, ContractError::TimeRemaining {}));
+
+        // once everything is withdrawn the schedule is gone
+        let res = execute(deps.as_mut(), later(5000), mock_info("owner1", &[]), ExecuteMsg::WithdrawVested {}).unwrap();
+        assert_eq!(res.messages[0].msg, send("owner1", 750));
+        assert!(query_vesting_info(deps.as_ref(), later(5000), "owner1".to_string()).unwrap().tranches.is_empty());
+    }
+
+    #[test]
+    fn tranches_stay_bounded() {
+        let tranche = |amount, start, end, withdrawn| VestingTranche {
+            amount: Uint128::new(amount),
+            start,
+            end,
+            withdrawn: Uint128::new(withdrawn),
+        };
+
+        // a claim ending with the newest tranche joins it on the same schedule
+        let tranches = add_tranche(vec![tranche(1000, 0, 1000, 100)], Uint128::new(500), 250, 1000).unwrap();
+        assert_eq!(tranches, [tranche(150, 250, 250, 0), tranche(1250, 250, 1000, 0)]);
+        let (withdrawable, locked) = vesting_totals(&tranches, 500).unwrap();
+        assert_eq!((withdrawable, locked), (Uint128::new(150 + 250 + 166), Uint128::new(834)));
+
+        // finished tranches collapse into one, and past the limit claims join the newest
+        let mut tranches = vec![tranche(100, 0, 50, 40)];
+        for now in 100..100 + 2 * MAX_TRANCHES as u64 {
+            tranches = add_tranche(tranches, Uint128::new(100), now, now + 1000).unwrap();
+        }
+        assert_eq!(tranches.len(), MAX_TRANCHES);
+        let now = 100 + 2 * MAX_TRANCHES as u64;
+        assert!(tranches[0].end <= now && tranches[0].amount >= Uint128::new(60));
+        let (withdrawable, locked) = vesting_totals(&tranches, now).unwrap();
+        assert_eq!(withdrawable + locked, Uint128::new(60 + 2000));
+        // nothing vests sooner than its own claim would have
+        assert!(tranches.iter().all(|tranche| tranche.end >= tranche.start + 1000 || tranche.end <= now));
+    }
+
+    #[test]
+    fn vesting_rewards_cannot_be_withdrawn_by_the_owner() {
+        let policy = VestingPolicy { duration: 1000, instant_haircut: 20, destination: PenaltyDestination::Redistribute {} };
+        let mut deps = setup(policy);
+        deps.querier.update_balance(mock_env().contract.address, coins(1500, "ujuno"));
+        execute(deps.as_mut(), later(100), mock_info("owner1", &[]), claim(false)).unwrap();
+
+        let msg = ExecuteMsg::WithdrawAllMoney { amount_juno: Uint128::new(501) };
+        let err = execute(deps.as_mut(), later(100), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::VestingLocked { .. }));
+        let msg = ExecuteMsg::WithdrawAllMoney { amount_juno: Uint128::new(500) };
+        execute(deps.as_mut(), later(100), mock_info("creator", &[]), msg).unwrap();
+
+        // withdrawn rewards release the lock
+        deps.querier.update_balance(mock_env().contract.address, coins(1000, "ujuno"));
+        execute(deps.as_mut(), later(350), mock_info("owner1", &[]), ExecuteMsg::WithdrawVested {}).unwrap();
+        assert_eq!(VESTING_LOCKED.load(&deps.storage).unwrap(), Uint128::new(750));
+        deps.querier.update_balance(mock_env().contract.address, coins(750, "ujuno"));
+        let msg = ExecuteMsg::WithdrawAllMoney { amount_juno: Uint128::new(1) };
+        let err = execute(deps.as_mut(), later(350), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::VestingLocked { .. }));
+    }
+
+    #[test]
+    fn instant_claims_pay_a_haircut() {
+        let treasury = PenaltyDestination::Treasury { address: "treasury".to_string() };
+        let mut deps = setup(VestingPolicy { duration: 1000, instant_haircut: 20, destination: treasury });
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), claim(true)).unwrap();
+        let sent: Vec<_> = res.messages.into_iter().map(|msg| msg.msg).collect();
+        assert_eq!(sent, [send("owner1", 800), send("treasury", 200)]);
+
+        let policy = VestingPolicy { duration: 1000, instant_haircut: 20, destination: PenaltyDestination::Redistribute {} };
+        let mut deps = setup(policy);
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), claim(true)).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, send("owner1", 800));
+        assert_eq!(query_state_info(deps.as_ref()).unwrap().reward_dust, Uint128::new(200));
+    }
+
+    #[test]
+    fn every_reward_payout_is_settled() {
+        let treasury = PenaltyDestination::Treasury { address: "treasury".to_string() };
+        let mut deps = setup(VestingPolicy { duration: 1000, instant_haircut: 20, destination: treasury });
+        let locked = |deps: &OwnedDeps<_, _, _>, owner: &str, seconds| {
+            query_vesting_info(deps.as_ref(), later(seconds), owner.to_string()).unwrap().locked
+        };
+
+        // handing a position over vests its reward for the previous owner
+        let msg = ExecuteMsg::TransferStake { token_id: "1".to_string(), recipient: "owner2".to_string() };
+        let res = execute(deps.as_mut(), later(100), mock_info("owner1", &[]), msg).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(locked(&deps, "owner1", 100), Uint128::new(1000));
+
+        // withdrawing only sends the NFT back
+        let info = mock_info("reward_wallet", &coins(1000, "ujuno"));
+        execute(deps.as_mut(), later(200), info, ExecuteMsg::DistributeReward {}).unwrap();
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), later(200), mock_info("owner2", &[]), msg).unwrap();
+        let msg = ExecuteMsg::WithdrawNft { token_id: "1".to_string() };
+        let res = execute(deps.as_mut(), later(1200), mock_info("owner2", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(locked(&deps, "owner2", 1200), Uint128::new(1000));
+
+        // pool rewards take the haircut when claimed instantly, and vest on the last withdrawal
+        let msg = ExecuteMsg::SetPool { asset: PoolAsset::Native { denom: "ugov".to_string() }, weight: 1 };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("owner3", &coins(300, "ugov"));
+        execute(deps.as_mut(), later(1200), info, ExecuteMsg::BondNative {}).unwrap();
+        let info = mock_info("reward_wallet", &coins(1000, "ujuno"));
+        execute(deps.as_mut(), later(1300), info, ExecuteMsg::DistributeReward {}).unwrap();
+        let msg = ExecuteMsg::ClaimPoolRewards { pool: "ugov".to_string(), instant: Some(true) };
+        let res = execute(deps.as_mut(), later(1300), mock_info("owner3", &[]), msg).unwrap();
+        let sent: Vec<_> = res.messages.into_iter().map(|msg| msg.msg).collect();
+        assert_eq!(sent, [send("owner3", 800), send("treasury", 200)]);
+
+        let msg = ExecuteMsg::Unbond { pool: "ugov".to_string(), amount: Uint128::new(300) };
+        execute(deps.as_mut(), later(1300), mock_info("owner3", &[]), msg).unwrap();
+        let info = mock_info("reward_wallet", &coins(1000, "ujuno"));
+        execute(deps.as_mut(), later(1400), info, ExecuteMsg::DistributeReward {}).unwrap();
+        let msg = ExecuteMsg::WithdrawUnbonded { pool: "ugov".to_string() };
+        let res = execute(deps.as_mut(), later(5000), mock_info("owner3", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(locked(&deps, "owner3", 5000), Uint128::new(1000));
+    }
+}
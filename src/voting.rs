@@ -0,0 +1,132 @@
+use cosmwasm_std::{Deps, Env, StdResult, Storage, Uint128};
+
+use crate::error::ContractError;
+use crate::msg::{TotalPowerAtHeightResponse, VotingPowerAtHeightResponse};
+use crate::state::{STAKED_POWER, TOTAL_POWER};
+
+/// Every token in "Staked" status is worth one vote to its owner, tokens that
+/// are unstaking count as zero. Called whenever a token enters that status or
+/// changes hands in it.
+pub(crate) fn add_power(storage: &mut dyn Storage, owner: &str, height: u64) -> Result<(), ContractError> {
+    STAKED_POWER.update(storage, owner, height, |power| -> Result<_, ContractError> {
+        Ok(power.unwrap_or_default().checked_add(Uint128::new(1))?)
+    })?;
+    TOTAL_POWER.update(storage, height, |power| -> Result<_, ContractError> {
+        Ok(power.unwrap_or_default().checked_add(Uint128::new(1))?)
+    })?;
+    Ok(())
+}
+
+/// Counterpart of `add_power` for a token leaving "Staked" or its owner.
+pub(crate) fn remove_power(storage: &mut dyn Storage, owner: &str, height: u64) -> Result<(), ContractError> {
+    STAKED_POWER.update(storage, owner, height, |power| -> Result<_, ContractError> {
+        Ok(power.unwrap_or_default().checked_sub(Uint128::new(1))?)
+    })?;
+    TOTAL_POWER.update(storage, height, |power| -> Result<_, ContractError> {
+        Ok(power.unwrap_or_default().checked_sub(Uint128::new(1))?)
+    })?;
+    Ok(())
+}
+
+/// Power as it was at the start of block `height`, defaulting to the current
+/// block like the DAO voting module interface expects.
+pub fn query_voting_power_at_height(
+    deps: Deps,
+    env: Env,
+    address: String,
+    height: Option<u64>,
+) -> StdResult<VotingPowerAtHeightResponse> {
+    let height = height.unwrap_or(env.block.height);
+    let power = STAKED_POWER.may_load_at_height(deps.storage, &address, height)?.unwrap_or_default();
+    Ok(VotingPowerAtHeightResponse { power, height })
+}
+
+pub fn query_total_power_at_height(
+    deps: Deps,
+    env: Env,
+    height: Option<u64>,
+) -> StdResult<TotalPowerAtHeightResponse> {
+    let height = height.unwrap_or(env.block.height);
+    let power = TOTAL_POWER.may_load_at_height(deps.storage, height)?.unwrap_or_default();
+    Ok(TotalPowerAtHeightResponse { power, height })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::instantiate_msg;
+    use crate::contract::{execute, instantiate, query};
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{from_json, to_json_binary, DepsMut};
+    use cw721::Cw721ReceiveMsg;
+
+    fn at_height(height: u64) -> Env {
+        let mut env = mock_env();
+        env.block.height = height;
+        env
+    }
+
+    fn stake(deps: DepsMut, height: u64, owner: &str, token_id: &str) {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: owner.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&"").unwrap(),
+        });
+        execute(deps, at_height(height), mock_info("nft_address1", &[]), msg).unwrap();
+    }
+
+    fn power(deps: Deps, address: &str, height: u64) -> u128 {
+        let msg = QueryMsg::VotingPowerAtHeight { address: address.to_string(), height: Some(height) };
+        let res: VotingPowerAtHeightResponse = from_json(query(deps, mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.height, height);
+        res.power.u128()
+    }
+
+    fn total(deps: Deps, height: u64) -> u128 {
+        let msg = QueryMsg::TotalPowerAtHeight { height: Some(height) };
+        let res: TotalPowerAtHeightResponse = from_json(query(deps, mock_env(), msg).unwrap()).unwrap();
+        res.power.u128()
+    }
+
+    #[test]
+    fn power_is_snapshotted_per_height() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), at_height(10), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        stake(deps.as_mut(), 10, "owner1", "1");
+        stake(deps.as_mut(), 10, "owner1", "2");
+        stake(deps.as_mut(), 11, "owner2", "3");
+
+        // a height sees the state at the start of its block
+        assert_eq!((power(deps.as_ref(), "owner1", 10), total(deps.as_ref(), 10)), (0, 0));
+        assert_eq!((power(deps.as_ref(), "owner1", 11), total(deps.as_ref(), 11)), (2, 2));
+        assert_eq!((power(deps.as_ref(), "owner2", 12), total(deps.as_ref(), 12)), (1, 3));
+
+        // unstaking tokens count as zero
+        let msg = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+        execute(deps.as_mut(), at_height(20), mock_info("owner1", &[]), msg).unwrap();
+        assert_eq!((power(deps.as_ref(), "owner1", 20), total(deps.as_ref(), 20)), (2, 3));
+        assert_eq!((power(deps.as_ref(), "owner1", 21), total(deps.as_ref(), 21)), (1, 2));
+
+        // transfers move the power, unstaking tokens have none to move
+        let allow = ExecuteMsg::UpdateConfig(crate::msg::UpdateConfigMsg {
+            transfer_unstaking: Some(true),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), at_height(30), mock_info("creator", &[]), allow).unwrap();
+        for token_id in ["1", "2"] {
+            let msg = ExecuteMsg::TransferStake { token_id: token_id.to_string(), recipient: "owner2".to_string() };
+            execute(deps.as_mut(), at_height(30), mock_info("owner1", &[]), msg).unwrap();
+        }
+        assert_eq!(power(deps.as_ref(), "owner1", 31), 0);
+        assert_eq!(power(deps.as_ref(), "owner2", 31), 2);
+        assert_eq!(power(deps.as_ref(), "owner2", 30), 1);
+        assert_eq!(total(deps.as_ref(), 31), 2);
+
+        // without a height the current block is used
+        let msg = QueryMsg::VotingPowerAtHeight { address: "owner2".to_string(), height: None };
+        let res: VotingPowerAtHeightResponse = from_json(query(deps.as_ref(), at_height(40), msg).unwrap()).unwrap();
+        assert_eq!(res, VotingPowerAtHeightResponse { power: Uint128::new(2), height: 40 });
+        assert_eq!(power(deps.as_ref(), "nobody", 40), 0);
+    }
+}
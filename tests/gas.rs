@@ -0,0 +1,200 @@
+//! Gas benchmark running the compiled contract in cosmwasm-vm. It measures
+//! the main operations with a growing number of staked tokens and fails when
+//! one of them goes over its budget.
+//!
+//! It is ignored and nothing runs it automatically, the budgets are only
+//! checked by hand (see "Gas budgets" in Developing.md).
+//!
+//! It runs against the optimized `contract.wasm` produced by rust-optimizer
+//! (see Developing.md), then:
+//!
+//! ```sh
+//! cargo gas-bench
+//! ```
+//!
+//! A plain `cargo wasm` build from a recent compiler is not accepted by the
+//! VM, it uses Wasm features beyond the MVP that chains do not enable.
+//!
+//! The VM is driven through its raw entry points with JSON encoded
+//! arguments, so the contract's own cosmwasm-std types can be used.
+
+use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::{coins, from_json, to_json_binary, to_json_vec, ContractResult, Empty, Env, MessageInfo, Response};
+use cosmwasm_vm::testing::{
+    mock_instance_with_options, MockApi, MockInstanceOptions, MockQuerier, MockStorage,
+};
+use cosmwasm_vm::{call_execute_raw, call_instantiate_raw, Instance};
+use cw721::Cw721ReceiveMsg;
+
+use Hope_Contract::msg::{ExecuteMsg, InstantiateMsg};
+
+const WASM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/contract.wasm");
+const STAKED_TOKENS: &[usize] = &[10, 100, 1_000, 10_000];
+const STAKING_PERIOD: u64 = 1000;
+const DISTRIBUTE_PERIOD: u64 = 100;
+
+/// wasmd's default conversion from CosmWasm 1.x gas to SDK gas.
+const GAS_MULTIPLIER: u64 = 140_000_000;
+
+/// Budgets in SDK gas, about twice what was measured with 10,000 tokens. They
+/// only cover the Wasm execution metered by the VM, the chain charges storage
+/// access on top.
+const STAKE_BUDGET: u64 = 2_500;
+const CLAIM_BUDGET: u64 = 1_000;
+const WITHDRAW_BUDGET: u64 = 2_500;
+/// `DistributeReward` touches every staked token, so it is budgeted per token
+/// on top of a fixed base. It measured about 84 per token.
+const DISTRIBUTE_BASE_BUDGET: u64 = 2_000;
+const DISTRIBUTE_BUDGET_PER_TOKEN: u64 = 150;
+
+type VmInstance = Instance<MockApi, MockStorage, MockQuerier>;
+
+// The wasmer release behind cosmwasm-vm 1.5 links compiled code against
+// `__rust_probestack`, which current compilers no longer export. This is the
+// x86_64 implementation from compiler-builtins.
+#[cfg(target_arch = "x86_64")]
+std::arch::global_asm!(
+    ".globl __rust_probestack",
+    "__rust_probestack:",
+    "    push rbp",
+    "    mov rbp, rsp",
+    "    mov r11, rax",
+    "    cmp r11, 0x1000",
+    "    jna 3f",
+    "2:",
+    "    sub rsp, 0x1000",
+    "    test qword ptr [rsp + 8], rsp",
+    "    sub r11, 0x1000",
+    "    cmp r11, 0x1000",
+    "    ja 2b",
+    "3:",
+    "    sub rsp, r11",
+    "    test qword ptr [rsp + 8], rsp",
+    "    add rsp, rax",
+    "    leave",
+    "    ret",
+);
+
+struct Bench {
+    instance: VmInstance,
+    env: Env,
+}
+
+impl Bench {
+    fn new(wasm: &[u8]) -> Self {
+        let mut instance = mock_instance_with_options(
+            wasm,
+            MockInstanceOptions {
+                gas_limit: u64::MAX / 2,
+                memory_limit: None,
+                ..MockInstanceOptions::default()
+            },
+        );
+        let msg = InstantiateMsg {
+            denom: "ujuno".to_string(),
+            staking_period: STAKING_PERIOD,
+            reward_wallet: "reward_wallet".to_string(),
+            distribute_period: DISTRIBUTE_PERIOD,
+            owner: None,
+            nft_address: "nft_address".to_string(),
+            token_address: None,
+            can_stake: true,
+            min_stake_age: None,
+            nft_weight: None,
+            transfer_unstaking: None,
+            points_rate: None,
+            reward_expiry: None,
+            receipts: None,
+        };
+        let (env, info) = encode(&mock_env(), &mock_info("creator", &[]));
+        let res = call_instantiate_raw(&mut instance, &env, &info, &to_json_vec(&msg).unwrap()).unwrap();
+        unwrap_response(&res);
+        Bench { instance, env: mock_env() }
+    }
+
+    /// Runs `msg` and returns the SDK gas it used.
+    fn gas(&mut self, sender: &str, msg: ExecuteMsg, funds: u128) -> u64 {
+        let funds = if funds == 0 { vec![] } else { coins(funds, "ujuno") };
+        let (env, info) = encode(&self.env, &mock_info(sender, &funds));
+        let before = self.instance.get_gas_left();
+        let res = call_execute_raw(&mut self.instance, &env, &info, &to_json_vec(&msg).unwrap()).unwrap();
+        let used = before - self.instance.get_gas_left();
+        unwrap_response(&res);
+        used / GAS_MULTIPLIER
+    }
+
+    fn stake(&mut self, token_id: usize) -> u64 {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: format!("staker{}", token_id % 50),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&Empty {}).unwrap(),
+        });
+        self.gas("nft_address", msg, 0)
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        self.env.block.time = self.env.block.time.plus_seconds(seconds);
+    }
+}
+
+fn encode(env: &Env, info: &MessageInfo) -> (Vec<u8>, Vec<u8>) {
+    (to_json_vec(env).unwrap(), to_json_vec(info).unwrap())
+}
+
+fn unwrap_response(raw: &[u8]) {
+    if let ContractResult::Err(err) = from_json::<ContractResult<Response<Empty>>>(raw).unwrap() {
+        panic!("{}", err);
+    }
+}
+
+struct Measurement {
+    tokens: usize,
+    stake: u64,
+    distribute: u64,
+    claim: u64,
+    withdraw: u64,
+}
+
+fn measure(wasm: &[u8], tokens: usize) -> Measurement {
+    let mut bench = Bench::new(wasm);
+    let mut stake = 0;
+    for token_id in 0..tokens {
+        stake = bench.stake(token_id);
+    }
+
+    bench.advance(DISTRIBUTE_PERIOD);
+    let distribute = bench.gas("reward_wallet", ExecuteMsg::DistributeReward {}, 1_000_000_000);
+    let claim = bench.gas("staker0", ExecuteMsg::GetReward { token_ids: vec!["0".to_string()], instant: None }, 0);
+
+    bench.gas("staker0", ExecuteMsg::UnstakeNft { token_id: "0".to_string() }, 0);
+    bench.advance(STAKING_PERIOD);
+    let withdraw = bench.gas("staker0", ExecuteMsg::WithdrawNft { token_id: "0".to_string() }, 0);
+
+    Measurement { tokens, stake, distribute, claim, withdraw }
+}
+
+#[test]
+#[ignore = "needs the optimized contract.wasm, run with `cargo gas-bench`"]
+fn gas_stays_within_budget() {
+    let wasm = std::fs::read(WASM).unwrap_or_else(|_| panic!("{} not found, see the module docs to build it", WASM));
+
+    println!("{:>8} {:>10} {:>12} {:>10} {:>10}", "tokens", "stake", "distribute", "claim", "withdraw");
+    let mut over_budget = vec![];
+    for &tokens in STAKED_TOKENS {
+        let m = measure(&wasm, tokens);
+        println!("{:>8} {:>10} {:>12} {:>10} {:>10}", m.tokens, m.stake, m.distribute, m.claim, m.withdraw);
+
+        let distribute_budget = DISTRIBUTE_BASE_BUDGET + DISTRIBUTE_BUDGET_PER_TOKEN * tokens as u64;
+        for (operation, used, budget) in [
+            ("stake", m.stake, STAKE_BUDGET),
+            ("distribute", m.distribute, distribute_budget),
+            ("claim", m.claim, CLAIM_BUDGET),
+            ("withdraw", m.withdraw, WITHDRAW_BUDGET),
+        ] {
+            if used > budget {
+                over_budget.push(format!("{} with {} tokens used {} gas, budget {}", operation, tokens, used, budget));
+            }
+        }
+    }
+    assert!(over_budget.is_empty(), "over budget:\n{}", over_budget.join("\n"));
+}
@@ -0,0 +1,605 @@
+//! End-to-end tests running the staking contract next to a real cw721-base
+//! collection and a cw20-base LP token in cw-multi-test.
+
+use cosmwasm_std::{
+    coins, to_json_binary, Addr, Binary, Coin, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+    StdResult, Uint128,
+};
+use cw20::{Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, BalanceResponse};
+use cw721::OwnerOfResponse;
+use cw_multi_test::{App, AppResponse, Contract, ContractWrapper, Executor};
+
+use Hope_Contract::msg::{
+    ExecuteMsg, InstantiateMsg, MyIdsResponse, MySetsResponse, PendingRewardsResponse, PointsResponse, QueryMsg,
+    ReceiptConfig, ReceiveMsg, UpdateConfigMsg, VotingPowerAtHeightResponse,
+};
+use Hope_Contract::state::{CollectionSet, PoolAsset, SetPieces, StakePosition, State};
+
+const OWNER: &str = "owner";
+const REWARD_WALLET: &str = "reward_wallet";
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+const CAROL: &str = "carol";
+const DENOM: &str = "ujuno";
+const STAKING_PERIOD: u64 = 1000;
+const DISTRIBUTE_PERIOD: u64 = 100;
+
+fn staking_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(
+        ContractWrapper::new(
+            Hope_Contract::contract::execute,
+            Hope_Contract::contract::instantiate,
+            Hope_Contract::contract::query,
+        )
+        .with_reply(Hope_Contract::contract::reply),
+    )
+}
+
+// the contract pulls in cw721-base as a library, so it has no entry points
+type Cw721 = cw721_base::Cw721Contract<'static, cw721_base::Extension, Empty>;
+
+fn cw721_execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: cw721_base::ExecuteMsg<cw721_base::Extension>,
+) -> Result<Response, cw721_base::ContractError> {
+    Cw721::default().execute(deps, env, info, msg)
+}
+
+fn cw721_instantiate(deps: DepsMut, env: Env, info: MessageInfo, msg: cw721_base::InstantiateMsg) -> StdResult<Response> {
+    Cw721::default().instantiate(deps, env, info, msg)
+}
+
+fn cw721_query(deps: Deps, env: Env, msg: cw721_base::QueryMsg) -> StdResult<Binary> {
+    Cw721::default().query(deps, env, msg)
+}
+
+fn cw721_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(cw721_execute, cw721_instantiate, cw721_query))
+}
+
+fn receipts_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        staking_receipts::contract::execute,
+        staking_receipts::contract::instantiate,
+        staking_receipts::contract::query,
+    ))
+}
+
+fn cw20_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        cw20_base::contract::execute,
+        cw20_base::contract::instantiate,
+        cw20_base::contract::query,
+    ))
+}
+
+type ContractFn = fn() -> Box<dyn Contract<Empty>>;
+
+struct Suite {
+    app: App,
+    staking: Addr,
+    nft: Addr,
+    lp_token: Addr,
+}
+
+impl Suite {
+    fn new() -> Self {
+        Suite::build(None)
+    }
+
+    /// A suite where staking mints receipts in a companion collection.
+    fn with_receipts() -> Self {
+        Suite::build(Some(receipts_contract))
+    }
+
+    /// Receipts in a plain cw721-base, which does not report transfers.
+    fn with_plain_receipts() -> Self {
+        Suite::build(Some(cw721_contract))
+    }
+
+    fn build(receipts: Option<ContractFn>) -> Self {
+        let mut app = App::new(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(REWARD_WALLET), coins(1_000_000, DENOM))
+                .unwrap();
+        });
+        let nft = instantiate_collection(&mut app, "Hope");
+
+        let cw20_id = app.store_code(cw20_contract());
+        let lp_token = app
+            .instantiate_contract(
+                cw20_id,
+                Addr::unchecked(OWNER),
+                &cw20_base::msg::InstantiateMsg {
+                    name: "Hope LP".to_string(),
+                    symbol: "HLP".to_string(),
+                    decimals: 6,
+                    initial_balances: vec![Cw20Coin { address: CAROL.to_string(), amount: Uint128::new(1000) }],
+                    mint: None,
+                    marketing: None,
+                },
+                &[],
+                "lp token",
+                None,
+            )
+            .unwrap();
+
+        let receipts = receipts.map(|contract| ReceiptConfig {
+            code_id: app.store_code(contract()),
+            name: "Staked Hope".to_string(),
+            symbol: "SHOPE".to_string(),
+        });
+        let staking_id = app.store_code(staking_contract());
+        let staking = app
+            .instantiate_contract(
+                staking_id,
+                Addr::unchecked(OWNER),
+                &InstantiateMsg {
+                    denom: DENOM.to_string(),
+                    staking_period: STAKING_PERIOD,
+                    reward_wallet: REWARD_WALLET.to_string(),
+                    distribute_period: DISTRIBUTE_PERIOD,
+                    owner: None,
+                    nft_address: nft.to_string(),
+                    token_address: None,
+                    can_stake: true,
+                    min_stake_age: None,
+                    nft_weight: None,
+                    transfer_unstaking: None,
+                    points_rate: None,
+                    reward_expiry: None,
+                    receipts,
+                },
+                &[],
+                "staking",
+                None,
+            )
+            .unwrap();
+
+        Suite { app, staking, nft, lp_token }
+    }
+
+    fn mint(&mut self, collection: &Addr, token_id: &str, owner: &str) {
+        let msg = cw721_base::ExecuteMsg::<cw721_base::Extension>::Mint(cw721_base::MintMsg {
+            token_id: token_id.to_string(),
+            owner: owner.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        self.app
+            .execute_contract(Addr::unchecked(OWNER), collection.clone(), &msg, &[])
+            .unwrap();
+    }
+
+    fn stake(&mut self, collection: &Addr, token_id: &str, owner: &str) -> anyhow::Result<AppResponse> {
+        let msg = cw721_base::ExecuteMsg::<cw721_base::Extension>::SendNft {
+            contract: self.staking.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&Empty {}).unwrap(),
+        };
+        self.app.execute_contract(Addr::unchecked(owner), collection.clone(), &msg, &[])
+    }
+
+    fn execute(&mut self, sender: &str, msg: ExecuteMsg, funds: &[Coin]) -> anyhow::Result<AppResponse> {
+        self.app
+            .execute_contract(Addr::unchecked(sender), self.staking.clone(), &msg, funds)
+    }
+
+    fn distribute(&mut self, amount: u128) -> anyhow::Result<AppResponse> {
+        self.execute(REWARD_WALLET, ExecuteMsg::DistributeReward {}, &coins(amount, DENOM))
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        self.app.update_block(|block| {
+            block.time = block.time.plus_seconds(seconds);
+            block.height += seconds / 5;
+        });
+    }
+
+    fn balance(&self, address: &str) -> u128 {
+        self.app.wrap().query_balance(address, DENOM).unwrap().amount.u128()
+    }
+
+    fn lp_balance(&self, address: &str) -> u128 {
+        let res: BalanceResponse = self
+            .app
+            .wrap()
+            .query_wasm_smart(&self.lp_token, &Cw20QueryMsg::Balance { address: address.to_string() })
+            .unwrap();
+        res.balance.u128()
+    }
+
+    fn nft_owner(&self, token_id: &str) -> String {
+        owner_of(&self.app, &self.nft, token_id).unwrap()
+    }
+
+    fn receipts(&self) -> Addr {
+        Addr::unchecked(self.state().receipt_address.unwrap())
+    }
+
+    fn query<T: serde::de::DeserializeOwned>(&self, msg: &QueryMsg) -> T {
+        self.app.wrap().query_wasm_smart(&self.staking, msg).unwrap()
+    }
+
+    fn state(&self) -> State {
+        self.query(&QueryMsg::GetStateInfo {})
+    }
+}
+
+fn instantiate_collection(app: &mut App, name: &str) -> Addr {
+    let code_id = app.store_code(cw721_contract());
+    app.instantiate_contract(
+        code_id,
+        Addr::unchecked(OWNER),
+        &cw721_base::InstantiateMsg {
+            name: name.to_string(),
+            symbol: name.to_uppercase(),
+            minter: OWNER.to_string(),
+        },
+        &[],
+        name,
+        None,
+    )
+    .unwrap()
+}
+
+fn owner_of(app: &App, collection: &Addr, token_id: &str) -> StdResult<String> {
+    let res: OwnerOfResponse = app.wrap().query_wasm_smart(
+        collection,
+        &cw721_base::QueryMsg::OwnerOf { token_id: token_id.to_string(), include_expired: None },
+    )?;
+    Ok(res.owner)
+}
+
+fn assert_error(res: anyhow::Result<AppResponse>, expected: &str) {
+    assert_eq!(res.unwrap_err().root_cause().to_string(), expected);
+}
+
+#[test]
+fn stake_distribute_and_claim() {
+    let mut suite = Suite::new();
+    let nft = suite.nft.clone();
+    suite.mint(&nft, "1", ALICE);
+    suite.mint(&nft, "2", BOB);
+
+    suite.stake(&nft, "1", ALICE).unwrap();
+    suite.stake(&nft, "2", BOB).unwrap();
+    assert_eq!(suite.nft_owner("1"), suite.staking.to_string());
+    assert_eq!(suite.nft_owner("2"), suite.staking.to_string());
+    assert_eq!(suite.state().total_staked, Uint128::new(2));
+    let ids: MyIdsResponse = suite.query(&QueryMsg::GetMyIds { address: ALICE.to_string() });
+    assert_eq!(ids.token_ids, vec!["1".to_string()]);
+
+    // once staked the collection no longer lets the previous owner send it
+    assert_error(suite.stake(&nft, "1", ALICE), "Unauthorized");
+
+    // the first round has to wait for the distribute period
+    assert_error(suite.distribute(1000), "Can not distribute");
+    suite.advance(DISTRIBUTE_PERIOD);
+    assert_error(
+        suite.execute(ALICE, ExecuteMsg::DistributeReward {}, &[]),
+        "Unauthorized",
+    );
+    suite.distribute(1000).unwrap();
+    assert_eq!(suite.balance(REWARD_WALLET), 999_000);
+    assert_eq!(suite.balance(suite.staking.as_str()), 1000);
+
+    let pending: PendingRewardsResponse =
+        suite.query(&QueryMsg::PendingRewards { address: ALICE.to_string() });
+    assert_eq!(pending.rewards, coins(500, DENOM));
+
+    assert_error(
+        suite.execute(BOB, ExecuteMsg::GetReward { token_ids: vec!["1".to_string()], instant: None }, &[]),
+        "Unauthorized",
+    );
+    suite
+        .execute(ALICE, ExecuteMsg::GetReward { token_ids: vec!["1".to_string()], instant: None }, &[])
+        .unwrap();
+    assert_eq!(suite.balance(ALICE), 500);
+    assert_eq!(suite.balance(suite.staking.as_str()), 500);
+
+    // claiming again pays nothing
+    suite
+        .execute(ALICE, ExecuteMsg::GetReward { token_ids: vec!["1".to_string()], instant: None }, &[])
+        .unwrap();
+    assert_eq!(suite.balance(ALICE), 500);
+}
+
+#[test]
+fn unstake_and_withdraw() {
+    let mut suite = Suite::new();
+    let nft = suite.nft.clone();
+    suite.mint(&nft, "1", ALICE);
+    suite.stake(&nft, "1", ALICE).unwrap();
+
+    assert_error(
+        suite.execute(BOB, ExecuteMsg::UnstakeNft { token_id: "1".to_string() }, &[]),
+        "Unauthorized",
+    );
+    assert_error(
+        suite.execute(ALICE, ExecuteMsg::WithdrawNft { token_id: "1".to_string() }, &[]),
+        "Stkaing process",
+    );
+    suite
+        .execute(ALICE, ExecuteMsg::UnstakeNft { token_id: "1".to_string() }, &[])
+        .unwrap();
+    assert_eq!(suite.state().total_staked, Uint128::zero());
+    assert_error(
+        suite.execute(ALICE, ExecuteMsg::UnstakeNft { token_id: "1".to_string() }, &[]),
+        "Stkaing process",
+    );
+
+    // an unstaking token keeps earning during the cooldown
+    suite.advance(DISTRIBUTE_PERIOD);
+    suite.distribute(300).unwrap();
+    assert_error(
+        suite.execute(ALICE, ExecuteMsg::WithdrawNft { token_id: "1".to_string() }, &[]),
+        "Time remaining yet",
+    );
+
+    suite.advance(STAKING_PERIOD);
+    suite
+        .execute(ALICE, ExecuteMsg::WithdrawNft { token_id: "1".to_string() }, &[])
+        .unwrap();
+    assert_eq!(suite.nft_owner("1"), ALICE);
+    assert_eq!(suite.balance(ALICE), 300);
+    assert_eq!(suite.balance(suite.staking.as_str()), 0);
+    let ids: MyIdsResponse = suite.query(&QueryMsg::GetMyIds { address: ALICE.to_string() });
+    assert!(ids.token_ids.is_empty());
+    assert_error(
+        suite.execute(ALICE, ExecuteMsg::WithdrawNft { token_id: "1".to_string() }, &[]),
+        "Not staked",
+    );
+}
+
+#[test]
+fn config_changes() {
+    let mut suite = Suite::new();
+    let nft = suite.nft.clone();
+    suite.mint(&nft, "1", ALICE);
+
+    let pause = ExecuteMsg::UpdateConfig(UpdateConfigMsg { can_stake: Some(false), ..UpdateConfigMsg::default() });
+    assert_error(suite.execute(ALICE, pause.clone(), &[]), "Unauthorized");
+    suite.execute(OWNER, pause, &[]).unwrap();
+    assert_error(suite.stake(&nft, "1", ALICE), "Can not stake");
+    assert_eq!(suite.nft_owner("1"), ALICE);
+
+    let resume = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        can_stake: Some(true),
+        distribute_period: Some(500),
+        ..UpdateConfigMsg::default()
+    });
+    suite.execute(OWNER, resume, &[]).unwrap();
+    suite.stake(&nft, "1", ALICE).unwrap();
+    suite.advance(DISTRIBUTE_PERIOD);
+    assert_error(suite.distribute(100), "Can not distribute");
+    suite.advance(500 - DISTRIBUTE_PERIOD);
+    suite.distribute(100).unwrap();
+
+    // rejected changes leave the config untouched
+    let invalid = ExecuteMsg::UpdateConfig(UpdateConfigMsg { staking_period: Some(0), ..UpdateConfigMsg::default() });
+    assert!(suite.execute(OWNER, invalid, &[]).is_err());
+    assert_eq!(suite.state().staking_period, STAKING_PERIOD);
+
+    suite
+        .execute(OWNER, ExecuteMsg::WithdrawAllMoney { amount_juno: Uint128::new(100) }, &[])
+        .unwrap();
+    assert_eq!(suite.balance(OWNER), 100);
+}
+
+#[test]
+fn rejects_other_collections() {
+    let mut suite = Suite::new();
+    let other = instantiate_collection(&mut suite.app, "Other");
+    suite.mint(&other, "1", ALICE);
+
+    assert_error(suite.stake(&other, "1", ALICE), "Wrong nft contract error");
+    let owner: OwnerOfResponse = suite
+        .app
+        .wrap()
+        .query_wasm_smart(&other, &cw721_base::QueryMsg::OwnerOf { token_id: "1".to_string(), include_expired: None })
+        .unwrap();
+    assert_eq!(owner.owner, ALICE);
+    assert_eq!(suite.state().total_staked, Uint128::zero());
+}
+
+#[test]
+fn cw20_lp_pool() {
+    let mut suite = Suite::new();
+    let nft = suite.nft.clone();
+    let lp_token = suite.lp_token.clone();
+    suite.mint(&nft, "1", ALICE);
+    suite.stake(&nft, "1", ALICE).unwrap();
+
+    let set_pool = ExecuteMsg::SetPool { asset: PoolAsset::Cw20 { address: lp_token.to_string() }, weight: 100 };
+    assert_error(suite.execute(ALICE, set_pool.clone(), &[]), "Unauthorized");
+    suite.execute(OWNER, set_pool, &[]).unwrap();
+
+    let bond = Cw20ExecuteMsg::Send {
+        contract: suite.staking.to_string(),
+        amount: Uint128::new(1000),
+        msg: to_json_binary(&ReceiveMsg::Bond {}).unwrap(),
+    };
+    suite
+        .app
+        .execute_contract(Addr::unchecked(CAROL), lp_token.clone(), &bond, &[])
+        .unwrap();
+    assert_eq!(suite.lp_balance(CAROL), 0);
+    assert_eq!(suite.lp_balance(suite.staking.as_str()), 1000);
+
+    // the NFT pool and the LP pool have the same weight
+    suite.advance(DISTRIBUTE_PERIOD);
+    suite.distribute(1000).unwrap();
+    let position: StakePosition = suite.query(&QueryMsg::Position {
+        pool: lp_token.to_string(),
+        address: CAROL.to_string(),
+    });
+    assert_eq!(position.reward_juno, Uint128::new(500));
+
+    suite
+        .execute(CAROL, ExecuteMsg::ClaimPoolRewards { pool: lp_token.to_string(), instant: None }, &[])
+        .unwrap();
+    assert_eq!(suite.balance(CAROL), 500);
+
+    let unbond = ExecuteMsg::Unbond { pool: lp_token.to_string(), amount: Uint128::new(1000) };
+    suite.execute(CAROL, unbond, &[]).unwrap();
+    let withdraw = ExecuteMsg::WithdrawUnbonded { pool: lp_token.to_string() };
+    assert_error(suite.execute(CAROL, withdraw.clone(), &[]), "Time remaining yet");
+    suite.advance(STAKING_PERIOD);
+    suite.execute(CAROL, withdraw, &[]).unwrap();
+    assert_eq!(suite.lp_balance(CAROL), 1000);
+    assert_eq!(suite.lp_balance(suite.staking.as_str()), 0);
+
+    // tokens sent from any other cw20 contract are refused
+    assert_error(
+        suite.execute(CAROL, ExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+            sender: CAROL.to_string(),
+            amount: Uint128::new(10),
+            msg: to_json_binary(&ReceiveMsg::Bond {}).unwrap(),
+        }), &[]),
+        &format!("No staking pool for {}", CAROL),
+    );
+}
+
+#[test]
+fn receipts_carry_the_position() {
+    let mut suite = Suite::with_receipts();
+    let nft = suite.nft.clone();
+    let receipts = suite.receipts();
+    suite.mint(&nft, "1", ALICE);
+
+    suite.stake(&nft, "1", ALICE).unwrap();
+    assert_eq!(suite.nft_owner("1"), suite.staking.to_string());
+    assert_eq!(owner_of(&suite.app, &receipts, "1").unwrap(), ALICE);
+
+    // the receipt is tradeable, and the position goes with it
+    let transfer = cw721_base::ExecuteMsg::<cw721_base::Extension>::TransferNft {
+        recipient: BOB.to_string(),
+        token_id: "1".to_string(),
+    };
+    suite
+        .app
+        .execute_contract(Addr::unchecked(ALICE), receipts.clone(), &transfer, &[])
+        .unwrap();
+    suite.advance(DISTRIBUTE_PERIOD);
+    suite.distribute(400).unwrap();
+    let claim = ExecuteMsg::GetReward { token_ids: vec!["1".to_string()], instant: None };
+    assert_error(suite.execute(ALICE, claim.clone(), &[]), "Unauthorized");
+    suite.execute(BOB, claim, &[]).unwrap();
+    assert_eq!(suite.balance(BOB), 400);
+    let ids: MyIdsResponse = suite.query(&QueryMsg::GetMyIds { address: BOB.to_string() });
+    assert_eq!(ids.token_ids, vec!["1".to_string()]);
+
+    let unstake = ExecuteMsg::UnstakeNft { token_id: "1".to_string() };
+    assert_error(suite.execute(ALICE, unstake.clone(), &[]), "Unauthorized");
+    suite.execute(BOB, unstake, &[]).unwrap();
+    suite.advance(STAKING_PERIOD);
+    assert_error(
+        suite.execute(BOB, ExecuteMsg::WithdrawNft { token_id: "1".to_string() }, &[]),
+        "Send the receipt back to withdraw this token",
+    );
+
+    // sending the receipt back closes the position and burns it
+    let redeem = cw721_base::ExecuteMsg::<cw721_base::Extension>::SendNft {
+        contract: suite.staking.to_string(),
+        token_id: "1".to_string(),
+        msg: to_json_binary(&Empty {}).unwrap(),
+    };
+    suite
+        .app
+        .execute_contract(Addr::unchecked(BOB), receipts.clone(), &redeem, &[])
+        .unwrap();
+    assert_eq!(suite.nft_owner("1"), BOB);
+    assert!(owner_of(&suite.app, &receipts, "1").is_err());
+    let ids: MyIdsResponse = suite.query(&QueryMsg::GetMyIds { address: BOB.to_string() });
+    assert!(ids.token_ids.is_empty());
+
+    // a staked NFT sent again mints a fresh receipt
+    suite.stake(&nft, "1", BOB).unwrap();
+    assert_eq!(owner_of(&suite.app, &receipts, "1").unwrap(), BOB);
+}
+
+/// Points, a two-token set that alice stakes, then sells the receipt of "1" to bob.
+fn stake_a_pair_and_sell_one(suite: &mut Suite) {
+    let nft = suite.nft.clone();
+    let points = ExecuteMsg::UpdateConfig(UpdateConfigMsg { points_rate: Some(1), ..UpdateConfigMsg::default() });
+    suite.execute(OWNER, points, &[]).unwrap();
+    let set = CollectionSet {
+        name: "pair".to_string(),
+        bonus_percent: 50,
+        pieces: SetPieces::Tokens { token_ids: vec!["1".to_string(), "2".to_string()] },
+    };
+    suite.execute(OWNER, ExecuteMsg::AddCollectionSet { set }, &[]).unwrap();
+    for token_id in ["1", "2"] {
+        suite.mint(&nft, token_id, ALICE);
+        suite.stake(&nft, token_id, ALICE).unwrap();
+    }
+    let sets: MySetsResponse = suite.query(&QueryMsg::MySets { address: ALICE.to_string() });
+    assert_eq!(sets.sets.len(), 1);
+
+    let transfer = cw721_base::ExecuteMsg::<cw721_base::Extension>::TransferNft {
+        recipient: BOB.to_string(),
+        token_id: "1".to_string(),
+    };
+    let receipts = suite.receipts();
+    suite
+        .app
+        .execute_contract(Addr::unchecked(ALICE), receipts, &transfer, &[])
+        .unwrap();
+}
+
+/// Voting power, points and completed sets of each (address, power, points).
+fn assert_positions(suite: &Suite, expected: [(&str, u128, u128); 2]) {
+    for (address, power, balance) in expected {
+        let voting: VotingPowerAtHeightResponse =
+            suite.query(&QueryMsg::VotingPowerAtHeight { address: address.to_string(), height: None });
+        assert_eq!(voting.power, Uint128::new(power));
+        let points: PointsResponse = suite.query(&QueryMsg::Points { address: address.to_string() });
+        assert_eq!(points.balance, Uint128::new(balance));
+        let sets: MySetsResponse = suite.query(&QueryMsg::MySets { address: address.to_string() });
+        assert!(sets.sets.is_empty());
+    }
+}
+
+#[test]
+fn traded_receipts_move_power_points_and_sets() {
+    let mut suite = Suite::with_receipts();
+    stake_a_pair_and_sell_one(&mut suite);
+
+    // the receipt collection reports the transfer, the position moves with it
+    let ids: MyIdsResponse = suite.query(&QueryMsg::GetMyIds { address: BOB.to_string() });
+    assert_eq!(ids.token_ids, vec!["1".to_string()]);
+    suite.advance(100);
+    assert_positions(&suite, [(ALICE, 1, 100), (BOB, 1, 100)]);
+
+    // so there is nothing left to sync
+    let sync = ExecuteMsg::SyncReceipts { token_ids: vec!["1".to_string(), "2".to_string()] };
+    let res = suite.execute(CAROL, sync, &[]).unwrap();
+    assert!(!res.events.iter().any(|event| event.attributes.iter().any(|attr| attr.key == "moved")));
+
+    // only the receipt collection reports transfers
+    let hook = ExecuteMsg::ReceiptTransferred { token_id: "2".to_string() };
+    assert_error(suite.execute(BOB, hook, &[]), "Unauthorized");
+}
+
+#[test]
+fn plain_receipts_move_positions_on_sync() {
+    let mut suite = Suite::with_plain_receipts();
+    stake_a_pair_and_sell_one(&mut suite);
+    suite.advance(100);
+
+    // anyone can hand the position over to the new holder of its receipt
+    let sync = ExecuteMsg::SyncReceipts { token_ids: vec!["1".to_string(), "2".to_string()] };
+    suite.execute(CAROL, sync.clone(), &[]).unwrap();
+    let ids: MyIdsResponse = suite.query(&QueryMsg::GetMyIds { address: BOB.to_string() });
+    assert_eq!(ids.token_ids, vec!["1".to_string()]);
+    suite.advance(100);
+    assert_positions(&suite, [(ALICE, 1, 300), (BOB, 1, 100)]);
+
+    // syncing again moves nothing
+    let res = suite.execute(CAROL, sync, &[]).unwrap();
+    assert!(!res.events.iter().any(|event| event.attributes.iter().any(|attr| attr.key == "moved")));
+}
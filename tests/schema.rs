@@ -0,0 +1,41 @@
+//! Fails when the committed files in `schema/` no longer match the messages,
+//! regenerate them with `cargo schema`.
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all};
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "../examples/schema.rs"]
+mod export;
+
+fn read_schemas(dir: &Path) -> BTreeMap<String, String> {
+    read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, read_to_string(&path).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn committed_schemas_are_up_to_date() {
+    let committed = read_schemas(&Path::new(env!("CARGO_MANIFEST_DIR")).join("schema"));
+
+    let out_dir = std::env::temp_dir().join(format!("hope-contract-schema-{}", std::process::id()));
+    create_dir_all(&out_dir).unwrap();
+    export::export_schemas(&out_dir);
+    let generated = read_schemas(&out_dir);
+    remove_dir_all(&out_dir).unwrap();
+
+    assert_eq!(
+        committed.keys().collect::<Vec<_>>(),
+        generated.keys().collect::<Vec<_>>(),
+        "schema/ has missing or extra files, run `cargo schema`"
+    );
+    for (name, schema) in generated.iter() {
+        assert!(committed[name] == *schema, "schema/{} is stale, run `cargo schema`", name);
+    }
+}